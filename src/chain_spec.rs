@@ -0,0 +1,278 @@
+//! Chain spec / genesis
+//! Human-editable description of a network (serde, so it can be loaded from
+//! JSON or TOML by the node) and the deterministic genesis state built from
+//! it: registry, epoch-0 snapshot, genesis beacon and genesis block id.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::crypto_kmac_consensus::kmac256_hash;
+use crate::delegation::AccountId;
+use crate::fork_choice::BlockId;
+use crate::pot::{
+    q_from_basis_points, EpochSnapshot, NodeId, PotParams, RandaoBeacon, Registry, TrustParams,
+    TrustState,
+};
+
+/// Consensus parameters in basis points (1.0 = 10000)
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConsensusSpec {
+    pub trust_alpha_bps: u32,
+    pub trust_beta_bps: u32,
+    pub trust_init_bps: u32,
+    pub lambda_bps: u32,
+    pub min_bond: u64,
+    pub slash_noreveal_bps: u32,
+    pub slots_per_epoch: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GenesisValidator {
+    /// hex, 32 bytes
+    pub id: String,
+    pub stake: u64,
+    /// hex; Falcon-512 public key used by the node to check block signatures
+    pub falcon_pk: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GenesisBalance {
+    /// hex, 32 bytes
+    pub account: String,
+    pub amount: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainSpec {
+    pub chain_id: String,
+    /// Bound into RANDAO commits and finality votes (0 = no separation)
+    #[serde(default)]
+    pub network_id: u32,
+    pub genesis_time_ms: u64,
+    pub consensus: ConsensusSpec,
+    pub validators: Vec<GenesisValidator>,
+    #[serde(default)]
+    pub balances: Vec<GenesisBalance>,
+}
+
+/// Genesis state derived from a `ChainSpec`
+pub struct Genesis {
+    pub block_id: BlockId,
+    pub params: PotParams,
+    pub slots_per_epoch: u64,
+    pub registry: Registry,
+    pub trust: TrustState,
+    pub beacon: RandaoBeacon,
+    pub snapshot: EpochSnapshot,
+    pub falcon_pks: BTreeMap<NodeId, Vec<u8>>,
+    pub balances: BTreeMap<AccountId, u64>,
+}
+
+fn hex32(s: &str) -> Result<[u8; 32], &'static str> {
+    let v = hex::decode(s.trim()).map_err(|_| "invalid hex")?;
+    v.try_into().map_err(|_| "expected 32-byte hex id")
+}
+
+fn bps_to_q(bps: u32) -> Result<u64, &'static str> {
+    if bps > 10_000 { return Err("basis points must be <= 10000"); }
+    Ok(q_from_basis_points(bps))
+}
+
+impl ConsensusSpec {
+    pub fn pot_params(&self) -> Result<PotParams, &'static str> {
+        let trust = TrustParams::new(
+            bps_to_q(self.trust_alpha_bps)?,
+            bps_to_q(self.trust_beta_bps)?,
+            bps_to_q(self.trust_init_bps)?,
+        )?;
+        if self.lambda_bps == 0 { return Err("lambda must be > 0"); }
+        if self.slash_noreveal_bps > 10_000 { return Err("slash bps must be <= 10000"); }
+        if self.slots_per_epoch == 0 { return Err("slots_per_epoch must be > 0"); }
+        Ok(PotParams {
+            trust,
+            lambda_q: q_from_basis_points(self.lambda_bps),
+            min_bond: self.min_bond,
+            slash_noreveal_bps: self.slash_noreveal_bps,
+        })
+    }
+}
+
+/// Genesis validators (stake, Falcon pk) and balances, decoded and sorted by id
+type DecodedGenesis = (BTreeMap<NodeId, (u64, Vec<u8>)>, BTreeMap<AccountId, u64>);
+
+impl ChainSpec {
+    fn decode(&self) -> Result<DecodedGenesis, &'static str> {
+        let mut validators = BTreeMap::new();
+        for v in &self.validators {
+            let id = hex32(&v.id)?;
+            let pk = hex::decode(v.falcon_pk.trim()).map_err(|_| "invalid falcon_pk hex")?;
+            if pk.is_empty() { return Err("empty falcon_pk"); }
+            if validators.insert(id, (v.stake, pk)).is_some() { return Err("duplicate genesis validator"); }
+        }
+        let mut balances = BTreeMap::new();
+        for b in &self.balances {
+            if balances.insert(hex32(&b.account)?, b.amount).is_some() { return Err("duplicate genesis balance"); }
+        }
+        Ok((validators, balances))
+    }
+
+    /// Deterministic genesis block id (commits to the whole spec).
+    /// Hashes decoded keys in id order, so hex case and list order don't matter.
+    pub fn genesis_id(&self) -> Result<BlockId, &'static str> {
+        let (validators, balances) = self.decode()?;
+        Ok(self.genesis_hash(&validators, &balances))
+    }
+
+    fn genesis_hash(&self, validators: &BTreeMap<NodeId, (u64, Vec<u8>)>, balances: &BTreeMap<AccountId, u64>) -> BlockId {
+        let c = &self.consensus;
+        let mut vals: Vec<u8> = Vec::new();
+        for (id, (stake, pk)) in validators {
+            vals.extend_from_slice(id);
+            vals.extend_from_slice(&stake.to_le_bytes());
+            vals.extend_from_slice(&(pk.len() as u64).to_le_bytes());
+            vals.extend_from_slice(pk);
+        }
+        let mut bals: Vec<u8> = Vec::new();
+        for (acc, amount) in balances {
+            bals.extend_from_slice(acc);
+            bals.extend_from_slice(&amount.to_le_bytes());
+        }
+        let params: Vec<u8> = [c.trust_alpha_bps, c.trust_beta_bps, c.trust_init_bps, c.lambda_bps, c.slash_noreveal_bps]
+            .iter()
+            .flat_map(|x| x.to_le_bytes())
+            .chain(c.min_bond.to_le_bytes())
+            .chain(c.slots_per_epoch.to_le_bytes())
+            .collect();
+        kmac256_hash(b"GENESIS.v2", &[
+            self.chain_id.as_bytes(),
+            &self.network_id.to_le_bytes(),
+            &self.genesis_time_ms.to_le_bytes(),
+            &params,
+            &vals,
+            &bals,
+        ])
+    }
+
+    pub fn build_genesis(&self) -> Result<Genesis, &'static str> {
+        if self.chain_id.trim().is_empty() { return Err("empty chain_id"); }
+        if self.validators.is_empty() { return Err("genesis needs at least one validator"); }
+        let params = self.consensus.pot_params()?;
+        let (validators, balances) = self.decode()?;
+
+        let mut registry = Registry::default();
+        let mut falcon_pks = BTreeMap::new();
+        for (id, (stake, pk)) in &validators {
+            if *stake < params.min_bond { return Err("genesis validator stake below min_bond"); }
+            registry.insert(*id, *stake, true);
+            falcon_pks.insert(*id, pk.clone());
+        }
+
+        let block_id = self.genesis_hash(&validators, &balances);
+        let trust = TrustState::default();
+        let beacon = RandaoBeacon::new(
+            params.slash_noreveal_bps,
+            kmac256_hash(b"GENESIS.beacon.v1", &[&block_id]),
+        ).with_network_id(self.network_id);
+        let snapshot = EpochSnapshot::build(0, &registry, &trust, &params.trust, params.min_bond);
+        Ok(Genesis {
+            block_id,
+            params,
+            slots_per_epoch: self.consensus.slots_per_epoch,
+            registry,
+            trust,
+            beacon,
+            snapshot,
+            falcon_pks,
+            balances,
+        })
+    }
+
+    /// Refuse to start on data written for another chain
+    pub fn check_existing(&self, stored_chain_id: &str, stored_genesis: &BlockId) -> Result<(), &'static str> {
+        if stored_chain_id != self.chain_id { return Err("chain_id mismatch with existing data"); }
+        if *stored_genesis != self.genesis_id()? { return Err("genesis mismatch with existing data"); }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec() -> ChainSpec {
+        ChainSpec {
+            chain_id: "tt-testnet-1".into(),
+            network_id: 1,
+            genesis_time_ms: 1_700_000_000_000,
+            consensus: ConsensusSpec {
+                trust_alpha_bps: 9_900,
+                trust_beta_bps: 100,
+                trust_init_bps: 1_000,
+                lambda_bps: 10_000,
+                min_bond: 100,
+                slash_noreveal_bps: 500,
+                slots_per_epoch: 32,
+            },
+            validators: vec![
+                GenesisValidator { id: hex::encode([1u8; 32]), stake: 1_000, falcon_pk: "aa".into() },
+                GenesisValidator { id: hex::encode([2u8; 32]), stake: 3_000, falcon_pk: "bb".into() },
+            ],
+            balances: vec![GenesisBalance { account: hex::encode([9u8; 32]), amount: 5 }],
+        }
+    }
+
+    #[test]
+    fn genesis_is_deterministic() {
+        let g1 = spec().build_genesis().unwrap();
+        let g2 = spec().build_genesis().unwrap();
+        assert_eq!(g1.block_id, g2.block_id);
+        assert_eq!(g1.snapshot.weights_root, g2.snapshot.weights_root);
+        assert_eq!(g1.snapshot.epoch, 0);
+        assert_eq!(g1.snapshot.order.len(), 2);
+        assert_eq!(g1.registry.stake(&[2u8; 32]), 3_000);
+        assert_eq!(g1.balances.get(&[9u8; 32]), Some(&5));
+        assert_eq!(g1.beacon.prev_beacon, g2.beacon.prev_beacon);
+        assert_eq!(g1.beacon.network_id, 1);
+
+        let mut other = spec();
+        other.validators[0].stake = 1_001;
+        assert_ne!(other.genesis_id().unwrap(), g1.block_id);
+        assert!(spec().check_existing("tt-testnet-1", &g1.block_id).is_ok());
+        assert!(spec().check_existing("tt-mainnet", &g1.block_id).is_err());
+        assert!(spec().check_existing("tt-testnet-1", &other.genesis_id().unwrap()).is_err());
+    }
+
+    #[test]
+    fn genesis_id_ignores_hex_case_and_order() {
+        let id = spec().genesis_id().unwrap();
+        let mut s = spec();
+        s.validators.reverse();
+        s.validators[0].id = format!(" {} ", s.validators[0].id.to_uppercase());
+        s.validators[1].falcon_pk = "AA".into();
+        assert_eq!(s.genesis_id().unwrap(), id);
+        assert_eq!(s.build_genesis().unwrap().block_id, id);
+
+        s.validators[1].falcon_pk = "aa00".into();
+        assert_ne!(s.genesis_id().unwrap(), id);
+        s.validators[1].falcon_pk = "a".into();
+        assert!(s.genesis_id().is_err());
+        assert!(s.check_existing("tt-testnet-1", &id).is_err());
+    }
+
+    #[test]
+    fn invalid_specs_rejected() {
+        let mut s = spec();
+        s.validators[1].id = s.validators[0].id.clone();
+        assert!(s.build_genesis().is_err());
+        let mut s = spec();
+        s.validators[0].stake = 10;
+        assert!(s.build_genesis().is_err());
+        let mut s = spec();
+        s.consensus.trust_alpha_bps = 10_001;
+        assert!(s.build_genesis().is_err());
+        let mut s = spec();
+        s.validators[0].id = "zz".into();
+        assert!(s.build_genesis().is_err());
+    }
+}
@@ -0,0 +1,212 @@
+//! Delegated stake (nominators backing validators)
+//! Effective stake = own bond + delegations. Snapshots built from
+//! `effective_registry` therefore commit to the total effective stake in their
+//! Merkle leaves, and sortition weight grows with delegations. `min_bond` is
+//! checked against the validator's own bond: delegations alone cannot get it
+//! into a snapshot.
+//! Slashing and rewards are shared pro-rata with delegators.
+
+use std::collections::BTreeMap;
+
+use crate::pot::{NodeId, RegEntry, Registry};
+use crate::staking::UnbondingEntry;
+
+pub type AccountId = [u8; 32];
+
+pub struct Delegations {
+    pub unbonding_epochs: u64,
+    /// validator -> delegator -> amount
+    by_validator: BTreeMap<NodeId, BTreeMap<AccountId, u64>>,
+    /// (delegator, validator) -> wycofane środki, wciąż podlegające slashingowi
+    unbonding: BTreeMap<(AccountId, NodeId), Vec<UnbondingEntry>>,
+}
+
+#[inline]
+fn cut_bps(amount: u64, bps: u128) -> u64 {
+    ((u128::from(amount) * bps) / 10_000) as u64
+}
+
+impl Delegations {
+    pub fn new(unbonding_epochs: u64) -> Self {
+        Self { unbonding_epochs, by_validator: BTreeMap::new(), unbonding: BTreeMap::new() }
+    }
+
+    #[inline]
+    pub fn delegation(&self, from: &AccountId, to: &NodeId) -> u64 {
+        self.by_validator.get(to).and_then(|m| m.get(from)).copied().unwrap_or(0)
+    }
+
+    #[inline]
+    pub fn delegated_to(&self, validator: &NodeId) -> u64 {
+        self.by_validator.get(validator)
+            .map(|m| m.values().fold(0u64, |a, v| a.saturating_add(*v)))
+            .unwrap_or(0)
+    }
+
+    #[inline]
+    pub fn effective_stake(&self, reg: &Registry, who: &NodeId) -> u64 {
+        reg.stake(who).saturating_add(self.delegated_to(who))
+    }
+
+    pub fn delegate(&mut self, from: AccountId, to: NodeId, amount: u64, reg: &Registry) -> Result<(), &'static str> {
+        if amount == 0 { return Err("delegation amount must be > 0"); }
+        if !reg.map.contains_key(&to) { return Err("unknown validator"); }
+        let d = self.by_validator.entry(to).or_default().entry(from).or_insert(0);
+        *d = d.checked_add(amount).ok_or("delegation overflow")?;
+        Ok(())
+    }
+
+    /// Start undelegating; funds are released after `unbonding_epochs`
+    pub fn undelegate(&mut self, from: AccountId, to: NodeId, amount: u64, current_epoch: u64) -> Result<(), &'static str> {
+        if amount == 0 { return Err("undelegation amount must be > 0"); }
+        let m = self.by_validator.get_mut(&to).ok_or("no such delegation")?;
+        let d = m.get_mut(&from).ok_or("no such delegation")?;
+        if amount > *d { return Err("undelegation exceeds delegated amount"); }
+        *d -= amount;
+        if *d == 0 { m.remove(&from); }
+        if m.is_empty() { self.by_validator.remove(&to); }
+        self.unbonding.entry((from, to)).or_default()
+            .push(UnbondingEntry { amount, release_epoch: current_epoch + self.unbonding_epochs });
+        Ok(())
+    }
+
+    /// Matured undelegations (delegator, amount), removed from the unbonding set
+    pub fn release(&mut self, epoch: u64) -> Vec<(AccountId, u64)> {
+        let mut out: BTreeMap<AccountId, u64> = BTreeMap::new();
+        self.unbonding.retain(|(from, _), v| {
+            let r: u64 = v.iter().filter(|u| u.release_epoch <= epoch).map(|u| u.amount).sum();
+            if r > 0 { *out.entry(*from).or_insert(0) += r; }
+            v.retain(|u| u.release_epoch > epoch);
+            !v.is_empty()
+        });
+        out.into_iter().collect()
+    }
+
+    /// Registry copy with stake replaced by effective stake (input for
+    /// `EpochSnapshot::build`). Validators whose own bond is below `min_bond`
+    /// are marked inactive, whatever is delegated to them.
+    pub fn effective_registry(&self, reg: &Registry, min_bond: u64) -> Registry {
+        let mut out = Registry::default();
+        for (who, e) in &reg.map {
+            let active = e.active && e.stake >= min_bond;
+            out.map.insert(*who, RegEntry { who: *who, stake: self.effective_stake(reg, who), active });
+        }
+        out
+    }
+
+    /// Slash delegations (and undelegations still unbonding) to `validator`
+    /// by the same bps as its own stake. Returns the total amount cut.
+    pub fn slash(&mut self, validator: &NodeId, bps: u32) -> u64 {
+        let bps = u128::from(bps.min(10_000));
+        let mut total = 0u64;
+        if let Some(m) = self.by_validator.get_mut(validator) {
+            for d in m.values_mut() {
+                let c = cut_bps(*d, bps);
+                *d -= c;
+                total = total.saturating_add(c);
+            }
+            m.retain(|_, d| *d > 0);
+        }
+        for ((_, v), entries) in self.unbonding.iter_mut() {
+            if v != validator { continue; }
+            for u in entries.iter_mut() {
+                let c = cut_bps(u.amount, bps);
+                u.amount -= c;
+                total = total.saturating_add(c);
+            }
+        }
+        total
+    }
+
+    /// Split `amount` earned by `validator`: commission first, the rest pro-rata
+    /// to own stake and delegations. Rounding dust goes to the validator.
+    /// Returns (validator share, [(delegator, share)]).
+    pub fn split_reward(
+        &self,
+        reg: &Registry,
+        validator: &NodeId,
+        amount: u64,
+        commission_bps: u32,
+    ) -> (u64, Vec<(AccountId, u64)>) {
+        let commission = cut_bps(amount, u128::from(commission_bps.min(10_000)));
+        let pool = amount - commission;
+        let total = u128::from(self.effective_stake(reg, validator));
+        let mut shares = Vec::new();
+        let mut paid = 0u64;
+        if let Some(m) = self.by_validator.get(validator) {
+            for (from, d) in m {
+                let s = (u128::from(pool) * u128::from(*d)).checked_div(total).unwrap_or(0) as u64;
+                if s > 0 { shares.push((*from, s)); }
+                paid += s;
+            }
+        }
+        (amount - paid, shares)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pot::{EpochSnapshot, TrustParams, TrustState, ONE_Q};
+
+    fn nid(n: u8) -> NodeId {
+        let mut id = [0u8; 32];
+        id[0] = n;
+        id
+    }
+
+    #[test]
+    fn delegations_raise_effective_stake() {
+        let (v1, v2, alice) = (nid(1), nid(2), nid(9));
+        let mut reg = Registry::default();
+        reg.insert(v1, 1000, true);
+        reg.insert(v2, 1000, true);
+        let mut d = Delegations::new(2);
+        assert!(d.delegate(alice, nid(3), 10, &reg).is_err());
+        d.delegate(alice, v1, 2000, &reg).unwrap();
+        assert_eq!(d.effective_stake(&reg, &v1), 3000);
+
+        let tp = TrustParams { alpha_q: ONE_Q, beta_q: 0, init_q: ONE_Q };
+        let snap = EpochSnapshot::build(1, &d.effective_registry(&reg, 0), &TrustState::default(), &tp, 0);
+        assert_eq!(snap.stake_q_of(&v1), 3 * (ONE_Q / 4));
+        assert_eq!(snap.stake_q_of(&v2), ONE_Q / 4);
+    }
+
+    #[test]
+    fn min_bond_applies_to_own_bond_only() {
+        let (v1, v2, alice) = (nid(1), nid(2), nid(9));
+        let mut reg = Registry::default();
+        reg.insert(v1, 100, true);
+        reg.insert(v2, 1000, true);
+        let mut d = Delegations::new(2);
+        d.delegate(alice, v1, 5000, &reg).unwrap();
+
+        // 100 własnego + 5000 delegacji nadal poniżej min_bond = 500
+        let tp = TrustParams { alpha_q: ONE_Q, beta_q: 0, init_q: ONE_Q };
+        let snap = EpochSnapshot::build(1, &d.effective_registry(&reg, 500), &TrustState::default(), &tp, 500);
+        assert_eq!(snap.order, vec![v2]);
+        assert_eq!(snap.stake_q_of(&v1), 0);
+        assert_eq!(snap.stake_q_of(&v2), ONE_Q);
+    }
+
+    #[test]
+    fn slashing_and_rewards_are_pro_rata() {
+        let (v, alice, bob) = (nid(1), nid(8), nid(9));
+        let mut reg = Registry::default();
+        reg.insert(v, 1000, true);
+        let mut d = Delegations::new(2);
+        d.delegate(alice, v, 2000, &reg).unwrap();
+        d.delegate(bob, v, 1000, &reg).unwrap();
+
+        // 10% prowizji, reszta 1:2:1
+        let (own, shares) = d.split_reward(&reg, &v, 1000, 1000);
+        assert_eq!(shares, vec![(alice, 450), (bob, 225)]);
+        assert_eq!(own, 325);
+
+        d.undelegate(bob, v, 1000, 5).unwrap();
+        assert_eq!(d.slash(&v, 1000), 300);
+        assert_eq!(d.delegation(&alice, &v), 1800);
+        assert!(d.release(6).is_empty());
+        assert_eq!(d.release(7), vec![(bob, 900)]);
+    }
+}
@@ -0,0 +1,266 @@
+//! Epoch transition pipeline
+//! Detects epoch boundaries from the slot number, finalizes RANDAO (with
+//! no-reveal slashing) for the finished epoch, applies queued staking changes
+//! and builds the next snapshot. Every slash also cuts the validator's stake
+//! that is still unbonding and the delegations backing it.
+//! Persisting the returned snapshot is left to the caller's storage layer.
+
+use crate::pot::{
+    finalize_epoch_and_slash, slash_equivocation, EpochSnapshot, NodeId, RandaoBeacon, Registry,
+    TrustParams, TrustState,
+};
+use crate::delegation::{AccountId, Delegations};
+use crate::staking::{Staking, StakingChanges};
+
+/// Result of crossing into `snapshot.epoch`
+#[derive(Clone, Debug)]
+pub struct EpochTransition {
+    /// Epoch whose RANDAO was finalized (None for the very first epoch)
+    pub finished_epoch: Option<u64>,
+    pub beacon: Option<[u8; 32]>,
+    /// Validators slashed for committing without revealing
+    pub slashed_noreveal: Vec<NodeId>,
+    /// Bond/unbond changes applied before the snapshot (empty without staking)
+    pub staking: StakingChanges,
+    /// Matured undelegations (delegator, amount) to pay out
+    pub released_delegations: Vec<(AccountId, u64)>,
+    pub snapshot: EpochSnapshot,
+}
+
+pub struct EpochManager {
+    pub slots_per_epoch: u64,
+    current: Option<u64>,
+    pub staking: Option<Staking>,
+    /// When set, snapshots commit to effective (own + delegated) stake
+    pub delegations: Option<Delegations>,
+}
+
+impl EpochManager {
+    pub fn new(slots_per_epoch: u64) -> Result<Self, &'static str> {
+        if slots_per_epoch == 0 { return Err("slots_per_epoch must be > 0"); }
+        Ok(Self { slots_per_epoch, current: None, staking: None, delegations: None })
+    }
+
+    /// Validator set changes are applied at every boundary before the snapshot
+    pub fn with_staking(mut self, staking: Staking) -> Self {
+        self.staking = Some(staking);
+        self
+    }
+
+    pub fn with_delegations(mut self, delegations: Delegations) -> Self {
+        self.delegations = Some(delegations);
+        self
+    }
+
+    /// Resume after restart at an epoch whose snapshot is already stored
+    pub fn resume(slots_per_epoch: u64, current_epoch: u64) -> Result<Self, &'static str> {
+        let mut m = Self::new(slots_per_epoch)?;
+        m.current = Some(current_epoch);
+        Ok(m)
+    }
+
+    #[inline]
+    pub fn epoch_of(&self, slot: u64) -> u64 { slot / self.slots_per_epoch }
+
+    #[inline]
+    pub fn current_epoch(&self) -> Option<u64> { self.current }
+
+    /// Cut everything backing `who` besides its registry stake by `bps`
+    fn slash_backing(&mut self, who: &NodeId, bps: u32) {
+        if let Some(st) = self.staking.as_mut() { st.slash_unbonding(who, bps); }
+        if let Some(d) = self.delegations.as_mut() { d.slash(who, bps); }
+    }
+
+    /// Equivocation slash: registry stake and trust (see `pot::slash_equivocation`)
+    /// plus its unbonding stake and the delegations backing `who`
+    pub fn slash_equivocation(
+        &mut self,
+        reg: &mut Registry,
+        trust: &mut TrustState,
+        who: &NodeId,
+        tp: TrustParams,
+        penalty_bps: u32,
+    ) {
+        slash_equivocation(reg, trust, who, tp, penalty_bps);
+        self.slash_backing(who, penalty_bps);
+    }
+
+    /// Wywoływane w każdym slocie. Jeśli zegar przeskoczył kilka epok, każda
+    /// pominięta epoka jest domykana po kolei, żeby beacon pozostał ciągły.
+    pub fn on_slot(
+        &mut self,
+        slot: u64,
+        reg: &mut Registry,
+        trust: &mut TrustState,
+        beacon: &mut RandaoBeacon,
+        tp: &TrustParams,
+        min_bond: u64,
+    ) -> Vec<EpochTransition> {
+        let target = self.epoch_of(slot);
+        let mut out = Vec::new();
+        loop {
+            let next = match self.current {
+                Some(e) if e >= target => break,
+                Some(e) => e + 1,
+                None => target,
+            };
+            let finished = self.current;
+            let (beacon_val, slashed_noreveal) = match finished {
+                Some(e) => {
+                    let missing = beacon.epochs.get(&e)
+                        .map(|ep| {
+                            let mut m: Vec<NodeId> = ep.commits.keys()
+                                .filter(|w| !ep.finalized && !ep.reveals.contains_key(*w))
+                                .copied()
+                                .collect();
+                            m.sort();
+                            m
+                        })
+                        .unwrap_or_default();
+                    (Some(finalize_epoch_and_slash(beacon, e, reg, trust, *tp)), missing)
+                }
+                None => (None, Vec::new()),
+            };
+            for who in &slashed_noreveal {
+                self.slash_backing(who, beacon.slash_noreveal_bps);
+            }
+            let staking = self.staking.as_mut()
+                .map(|st| st.on_epoch(next, reg))
+                .unwrap_or_default();
+            let released_delegations = self.delegations.as_mut()
+                .map(|d| d.release(next))
+                .unwrap_or_default();
+            let snapshot = match &self.delegations {
+                Some(d) => EpochSnapshot::build(next, &d.effective_registry(reg, min_bond), trust, tp, min_bond),
+                None => EpochSnapshot::build(next, reg, trust, tp, min_bond),
+            };
+            self.current = Some(next);
+            out.push(EpochTransition { finished_epoch: finished, beacon: beacon_val, slashed_noreveal, staking, released_delegations, snapshot });
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pot::{q_from_basis_points, ONE_Q};
+
+    fn nid(n: u8) -> NodeId {
+        let mut id = [0u8; 32];
+        id[0] = n;
+        id
+    }
+
+    #[test]
+    fn transitions_at_boundaries() {
+        let tp = TrustParams { alpha_q: ONE_Q, beta_q: 0, init_q: q_from_basis_points(1000) };
+        let (a, b) = (nid(1), nid(2));
+        let mut reg = Registry::default();
+        reg.insert(a, 1000, true);
+        reg.insert(b, 1000, true);
+        let mut ts = TrustState::default();
+        ts.set(a, ONE_Q);
+        ts.set(b, ONE_Q);
+        let mut beacon = RandaoBeacon::new(1000, [7u8; 32]);
+        let mut em = EpochManager::new(10).unwrap();
+
+        let t = em.on_slot(0, &mut reg, &mut ts, &mut beacon, &tp, 0);
+        assert_eq!(t.len(), 1);
+        assert_eq!(t[0].snapshot.epoch, 0);
+        assert!(t[0].finished_epoch.is_none());
+        assert!(em.on_slot(5, &mut reg, &mut ts, &mut beacon, &tp, 0).is_empty());
+
+        // a commituje i ujawnia, b tylko commituje
+        let ra = [1u8; 32];
+        beacon.commit(0, a, RandaoBeacon::commit_hash(0, &a, &ra));
+        assert!(beacon.reveal(0, a, ra));
+        beacon.commit(0, b, RandaoBeacon::commit_hash(0, &b, &[2u8; 32]));
+
+        let t = em.on_slot(10, &mut reg, &mut ts, &mut beacon, &tp, 0);
+        assert_eq!(t.len(), 1);
+        assert_eq!(t[0].finished_epoch, Some(0));
+        assert_eq!(t[0].slashed_noreveal, vec![b]);
+        assert_eq!(reg.stake(&b), 900);
+        assert_eq!(t[0].snapshot.epoch, 1);
+
+        // przeskok o dwie epoki
+        let t = em.on_slot(35, &mut reg, &mut ts, &mut beacon, &tp, 0);
+        assert_eq!(t.iter().map(|x| x.snapshot.epoch).collect::<Vec<_>>(), vec![2, 3]);
+        assert_eq!(em.current_epoch(), Some(3));
+    }
+
+    #[test]
+    fn staking_changes_enter_next_snapshot() {
+        use crate::staking::StakingOp;
+        let tp = TrustParams { alpha_q: ONE_Q, beta_q: 0, init_q: ONE_Q };
+        let (a, b) = (nid(1), nid(2));
+        let mut reg = Registry::default();
+        reg.insert(a, 1000, true);
+        let mut ts = TrustState::default();
+        let mut beacon = RandaoBeacon::new(0, [7u8; 32]);
+        let mut em = EpochManager::new(10).unwrap().with_staking(Staking::new(1));
+        em.on_slot(0, &mut reg, &mut ts, &mut beacon, &tp, 0);
+
+        em.staking.as_mut().unwrap().apply(StakingOp::Bond { who: b, amount: 1000 }, &reg).unwrap();
+        let t = em.on_slot(10, &mut reg, &mut ts, &mut beacon, &tp, 0);
+        assert_eq!(t[0].staking.activated, vec![b]);
+        assert_eq!(t[0].snapshot.order, vec![a, b]);
+        assert_eq!(t[0].snapshot.stake_q_of(&b), ONE_Q / 2);
+    }
+
+    #[test]
+    fn slashing_cuts_delegations_and_releases_undelegations() {
+        let tp = TrustParams { alpha_q: ONE_Q, beta_q: 0, init_q: q_from_basis_points(1000) };
+        let (v, alice, bob) = (nid(1), nid(8), nid(9));
+        let mut reg = Registry::default();
+        reg.insert(v, 1000, true);
+        let mut ts = TrustState::default();
+        let mut beacon = RandaoBeacon::new(1000, [7u8; 32]);
+        let mut d = Delegations::new(1);
+        d.delegate(alice, v, 2000, &reg).unwrap();
+        d.delegate(bob, v, 1000, &reg).unwrap();
+        let mut em = EpochManager::new(10).unwrap().with_delegations(d);
+        em.on_slot(0, &mut reg, &mut ts, &mut beacon, &tp, 0);
+
+        // v commituje bez ujawnienia, bob wycofuje delegację
+        beacon.commit(0, v, RandaoBeacon::commit_hash(0, &v, &[3u8; 32]));
+        em.delegations.as_mut().unwrap().undelegate(bob, v, 1000, 0).unwrap();
+        let t = em.on_slot(10, &mut reg, &mut ts, &mut beacon, &tp, 0);
+        assert_eq!(t[0].slashed_noreveal, vec![v]);
+        assert_eq!(reg.stake(&v), 900);
+        let d = em.delegations.as_ref().unwrap();
+        assert_eq!(d.delegation(&alice, &v), 1800);
+        // niedojrzałe wycofanie też obcięte, wypłata na granicy epoki 1
+        assert_eq!(t[0].released_delegations, vec![(bob, 900)]);
+
+        em.slash_equivocation(&mut reg, &mut ts, &v, tp, 5000);
+        assert_eq!(reg.stake(&v), 450);
+        assert_eq!(em.delegations.as_ref().unwrap().delegation(&alice, &v), 900);
+    }
+
+    #[test]
+    fn slashing_reaches_unbonding_stake() {
+        use crate::staking::StakingOp;
+        let tp = TrustParams { alpha_q: ONE_Q, beta_q: 0, init_q: ONE_Q };
+        let v = nid(1);
+        let mut reg = Registry::default();
+        reg.insert(v, 1000, true);
+        let mut ts = TrustState::default();
+        let mut beacon = RandaoBeacon::new(1000, [7u8; 32]);
+        let mut em = EpochManager::new(10).unwrap().with_staking(Staking::new(5));
+        em.on_slot(0, &mut reg, &mut ts, &mut beacon, &tp, 0);
+
+        // v wycofuje połowę i nie ujawnia RANDAO
+        em.staking.as_mut().unwrap().apply(StakingOp::Unbond { who: v, amount: 500 }, &reg).unwrap();
+        beacon.commit(0, v, RandaoBeacon::commit_hash(0, &v, &[3u8; 32]));
+        let t = em.on_slot(10, &mut reg, &mut ts, &mut beacon, &tp, 0);
+        assert_eq!(t[0].slashed_noreveal, vec![v]);
+        assert_eq!(em.staking.as_ref().unwrap().unbonding_total(&v), 450);
+        assert_eq!(reg.stake(&v), 450);
+
+        em.slash_equivocation(&mut reg, &mut ts, &v, tp, 5000);
+        assert_eq!(em.staking.as_ref().unwrap().unbonding_total(&v), 225);
+        assert_eq!(reg.stake(&v), 225);
+    }
+}
@@ -0,0 +1,375 @@
+//! Equivocation evidence, evidence pool and gossip proposal tracker
+//! Evidence = two conflicting signed headers of one validator for one slot.
+//! Signatures are checked through `HeaderVerifier` (Falcon with the validator
+//! keys in the node) both when evidence is gossiped and when a block includes it,
+//! so a proposer cannot slash anyone with made-up headers. Headers sign over
+//! the network id and the pool only accepts evidence from its own network, so
+//! headers replayed from another chain with the same key cannot slash here.
+
+use std::collections::{BTreeMap, HashSet};
+
+use crate::crypto_kmac_consensus::kmac256_hash;
+use crate::epoch::EpochManager;
+use crate::pot::{detect_equivocation, NodeId, Proposal, Registry, TrustParams, TrustState};
+
+/// Checks a proposer's signature over `SignedHeader::signing_msg`
+pub trait HeaderVerifier {
+    fn verify(&self, who: &NodeId, msg: &[u8; 32], sig: &[u8]) -> bool;
+}
+
+impl<F: Fn(&NodeId, &[u8; 32], &[u8]) -> bool> HeaderVerifier for F {
+    #[inline]
+    fn verify(&self, who: &NodeId, msg: &[u8; 32], sig: &[u8]) -> bool { self(who, msg, sig) }
+}
+
+/// Header reference plus the proposer's signature
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignedHeader {
+    pub network_id: u32,
+    pub proposal: Proposal,
+    pub sig: Vec<u8>,
+}
+
+impl SignedHeader {
+    /// Signed message; binds network, proposer and slot, so a header signed
+    /// for one slot (or chain) cannot be relabelled as a conflict in another
+    pub fn signing_msg(&self) -> [u8; 32] {
+        let p = &self.proposal;
+        kmac256_hash(b"HDR.sign.v2", &[
+            &self.network_id.to_le_bytes(),
+            &p.who,
+            &p.slot.to_le_bytes(),
+            &p.header_hash,
+        ])
+    }
+
+    #[inline]
+    pub fn verify(&self, v: &impl HeaderVerifier) -> bool {
+        v.verify(&self.proposal.who, &self.signing_msg(), &self.sig)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Evidence {
+    pub a: SignedHeader,
+    pub b: SignedHeader,
+}
+
+impl Evidence {
+    /// Build evidence in canonical order; None if the headers do not conflict
+    pub fn new(a: SignedHeader, b: SignedHeader) -> Option<Self> {
+        if a.network_id != b.network_id || !detect_equivocation(&[a.proposal, b.proposal]) { return None; }
+        let (a, b) = if a.proposal.header_hash <= b.proposal.header_hash { (a, b) } else { (b, a) };
+        Some(Self { a, b })
+    }
+
+    /// Structural check only (same network, proposer and slot, canonical order)
+    #[inline]
+    pub fn is_valid(&self) -> bool {
+        self.a.network_id == self.b.network_id
+            && self.a.proposal.header_hash < self.b.proposal.header_hash
+            && detect_equivocation(&[self.a.proposal, self.b.proposal])
+    }
+
+    /// Structural check plus both signatures
+    pub fn verify(&self, v: &impl HeaderVerifier) -> bool {
+        self.is_valid() && self.a.verify(v) && self.b.verify(v)
+    }
+
+    #[inline]
+    pub fn network_id(&self) -> u32 { self.a.network_id }
+
+    #[inline]
+    pub fn offender(&self) -> NodeId { self.a.proposal.who }
+
+    #[inline]
+    pub fn slot(&self) -> u64 { self.a.proposal.slot }
+
+    /// Id over the headers only; re-encoded signatures do not make new evidence
+    pub fn id(&self) -> [u8; 32] {
+        kmac256_hash(b"EVID.v3", &[
+            &self.a.network_id.to_le_bytes(),
+            &self.a.proposal.who,
+            &self.a.proposal.slot.to_le_bytes(),
+            &self.a.proposal.header_hash,
+            &self.b.proposal.header_hash,
+        ])
+    }
+}
+
+pub struct EvidencePool {
+    pending: BTreeMap<[u8; 32], Evidence>,
+    /// (who, slot) już ukarane – ta sama równoległa propozycja nie jest karana dwa razy
+    slashed: HashSet<(NodeId, u64)>,
+    pub network_id: u32,
+    pub max_age_epochs: u64,
+    pub slots_per_epoch: u64,
+}
+
+impl EvidencePool {
+    pub fn new(network_id: u32, max_age_epochs: u64, slots_per_epoch: u64) -> Result<Self, &'static str> {
+        if slots_per_epoch == 0 { return Err("slots_per_epoch must be > 0"); }
+        Ok(Self { pending: BTreeMap::new(), slashed: HashSet::new(), network_id, max_age_epochs, slots_per_epoch })
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize { self.pending.len() }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.pending.is_empty() }
+
+    /// Epoch of the equivocation, taken from the signed slot
+    #[inline]
+    pub fn epoch_of(&self, ev: &Evidence) -> u64 { ev.slot() / self.slots_per_epoch }
+
+    #[inline]
+    fn expired_slot(&self, slot: u64, current_epoch: u64) -> bool {
+        current_epoch.saturating_sub(slot / self.slots_per_epoch) > self.max_age_epochs
+    }
+
+    /// Add gossiped evidence. Ok(false) if already known or already slashed.
+    pub fn submit(&mut self, ev: Evidence, current_epoch: u64, verifier: &impl HeaderVerifier) -> Result<bool, &'static str> {
+        if !ev.is_valid() { return Err("evidence does not show equivocation"); }
+        if ev.network_id() != self.network_id { return Err("evidence from another network"); }
+        if self.epoch_of(&ev) > current_epoch { return Err("evidence from a future epoch"); }
+        if self.expired_slot(ev.slot(), current_epoch) { return Err("evidence expired"); }
+        if self.slashed.contains(&(ev.offender(), ev.slot())) { return Ok(false); }
+        let id = ev.id();
+        if self.pending.contains_key(&id) { return Ok(false); }
+        if !ev.verify(verifier) { return Err("invalid header signature in evidence"); }
+        self.pending.insert(id, ev);
+        Ok(true)
+    }
+
+    /// Drop evidence older than `max_age_epochs`; expired (who, slot) marks go
+    /// too, since evidence for them is rejected as expired anyway
+    pub fn prune(&mut self, current_epoch: u64) {
+        let (max_age, spe) = (self.max_age_epochs, self.slots_per_epoch);
+        let live = |slot: u64| current_epoch.saturating_sub(slot / spe) <= max_age;
+        self.pending.retain(|_, ev| live(ev.slot()));
+        self.slashed.retain(|(_, slot)| live(*slot));
+    }
+
+    /// Evidence to include in the next block (deterministic order by id)
+    pub fn pending_for_block(&self, max: usize) -> Vec<Evidence> {
+        self.pending.values().take(max).cloned().collect()
+    }
+
+    /// Process evidence included in a block: verify signatures, slash once per
+    /// (who, slot) through `em` (so delegations are cut too), remove from the
+    /// pool. Returns the slashed validators.
+    #[allow(clippy::too_many_arguments)]
+    pub fn apply_included(
+        &mut self,
+        included: &[Evidence],
+        verifier: &impl HeaderVerifier,
+        em: &mut EpochManager,
+        reg: &mut Registry,
+        trust: &mut TrustState,
+        tp: TrustParams,
+        penalty_bps: u32,
+        current_epoch: u64,
+    ) -> Vec<NodeId> {
+        let mut out = Vec::new();
+        for ev in included {
+            if ev.network_id() != self.network_id { continue; }
+            if self.epoch_of(ev) > current_epoch || self.expired_slot(ev.slot(), current_epoch) { continue; }
+            if self.slashed.contains(&(ev.offender(), ev.slot())) || !ev.verify(verifier) { continue; }
+            self.pending.remove(&ev.id());
+            self.slashed.insert((ev.offender(), ev.slot()));
+            em.slash_equivocation(reg, trust, &ev.offender(), tp, penalty_bps);
+            out.push(ev.offender());
+        }
+        out
+    }
+}
+
+/// Remembers the first header seen per (slot, proposer) from gossip and turns
+/// a conflicting one into evidence. Only slots within `window_slots` are kept.
+pub struct ProposalTracker {
+    seen: BTreeMap<(u64, NodeId), SignedHeader>,
+    detected: Vec<Evidence>,
+    pub window_slots: u64,
+}
+
+impl ProposalTracker {
+    pub fn new(window_slots: u64) -> Self {
+        Self { seen: BTreeMap::new(), detected: Vec::new(), window_slots }
+    }
+
+    /// Record a signature-checked header. Returns new evidence on the first
+    /// conflict for this (slot, proposer); later conflicts are not reported again.
+    pub fn observe(&mut self, h: SignedHeader) -> Option<Evidence> {
+        let p = h.proposal;
+        let first = self.seen.entry((p.slot, p.who)).or_insert_with(|| h.clone());
+        if first.proposal.header_hash == p.header_hash { return None; }
+        if self.detected.iter().any(|e| e.slot() == p.slot && e.offender() == p.who) { return None; }
+        let ev = Evidence::new(first.clone(), h)?;
+        self.detected.push(ev.clone());
+        Some(ev)
+    }
+
+    /// Equivocations detected so far (oldest first), e.g. for RPC
+    #[inline]
+    pub fn detected(&self) -> &[Evidence] { &self.detected }
+
+    pub fn prune(&mut self, current_slot: u64) {
+        let min = current_slot.saturating_sub(self.window_slots);
+        self.seen = self.seen.split_off(&(min, [0u8; 32]));
+        self.detected.retain(|e| e.slot() >= min);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pot::{q_from_basis_points, ONE_Q};
+
+    fn nid(n: u8) -> NodeId {
+        let mut id = [0u8; 32];
+        id[0] = n;
+        id
+    }
+
+    // zabawkowy podpis: w węźle jest to Falcon z kluczem walidatora
+    fn toy_sig(who: &NodeId, msg: &[u8; 32]) -> Vec<u8> {
+        kmac256_hash(b"TEST.sig", &[who, msg]).to_vec()
+    }
+
+    fn toy_verify(who: &NodeId, msg: &[u8; 32], sig: &[u8]) -> bool {
+        toy_sig(who, msg) == sig
+    }
+
+    fn signed(who: NodeId, slot: u64, hash: u8) -> SignedHeader {
+        signed_on(1, who, slot, hash)
+    }
+
+    fn signed_on(network_id: u32, who: NodeId, slot: u64, hash: u8) -> SignedHeader {
+        let mut h = SignedHeader { network_id, proposal: Proposal { who, slot, header_hash: [hash; 32] }, sig: Vec::new() };
+        h.sig = toy_sig(&who, &h.signing_msg());
+        h
+    }
+
+    fn conflicting(who: NodeId, slot: u64) -> (SignedHeader, SignedHeader) {
+        (signed(who, slot, 2), signed(who, slot, 1))
+    }
+
+    fn setup(who: NodeId) -> (TrustParams, Registry, TrustState, EpochManager) {
+        let tp = TrustParams { alpha_q: ONE_Q, beta_q: 0, init_q: q_from_basis_points(1000) };
+        let mut reg = Registry::default();
+        reg.insert(who, 1000, true);
+        let mut ts = TrustState::default();
+        ts.set(who, ONE_Q);
+        (tp, reg, ts, EpochManager::new(10).unwrap())
+    }
+
+    #[test]
+    fn evidence_is_canonical() {
+        let (a, b) = conflicting(nid(1), 7);
+        let e1 = Evidence::new(a.clone(), b.clone()).unwrap();
+        let e2 = Evidence::new(b, a.clone()).unwrap();
+        assert_eq!(e1, e2);
+        assert_eq!(e1.id(), e2.id());
+        assert!(e1.verify(&toy_verify));
+        assert!(Evidence::new(a.clone(), a).is_none());
+    }
+
+    #[test]
+    fn pool_expiry_and_slashing() {
+        let who = nid(1);
+        let (tp, mut reg, mut ts, mut em) = setup(who);
+
+        // slot 17 przy 10 slotach na epokę = epoka 1
+        let mut pool = EvidencePool::new(1, 2, 10).unwrap();
+        let (a, b) = conflicting(who, 17);
+        let ev = Evidence::new(a, b).unwrap();
+        assert_eq!(pool.epoch_of(&ev), 1);
+        assert!(pool.submit(ev.clone(), 0, &toy_verify).is_err());
+        assert_eq!(pool.submit(ev.clone(), 2, &toy_verify), Ok(true));
+        assert_eq!(pool.submit(ev.clone(), 2, &toy_verify), Ok(false));
+        assert!(pool.submit(ev.clone(), 4, &toy_verify).is_err());
+
+        let block = pool.pending_for_block(10);
+        assert_eq!(block.len(), 1);
+        let slashed = pool.apply_included(&block, &toy_verify, &mut em, &mut reg, &mut ts, tp, 5000, 2);
+        assert_eq!(slashed, vec![who]);
+        assert_eq!(reg.stake(&who), 500);
+        assert_eq!(ts.get(&who, 0), tp.init_q);
+        assert!(pool.is_empty());
+
+        // ponowne dołączenie nie karze drugi raz
+        assert!(pool.apply_included(&block, &toy_verify, &mut em, &mut reg, &mut ts, tp, 5000, 2).is_empty());
+        assert_eq!(pool.submit(ev.clone(), 2, &toy_verify), Ok(false));
+
+        let (c, d) = conflicting(nid(2), 19);
+        pool.submit(Evidence::new(c, d).unwrap(), 1, &toy_verify).unwrap();
+        pool.prune(4);
+        assert!(pool.is_empty());
+        assert!(pool.slashed.is_empty());
+    }
+
+    #[test]
+    fn forged_evidence_does_not_slash() {
+        let who = nid(1);
+        let (tp, mut reg, mut ts, mut em) = setup(who);
+        let mut pool = EvidencePool::new(1, 2, 10).unwrap();
+
+        let (a, mut b) = conflicting(who, 7);
+        b.sig = toy_sig(&nid(9), &b.signing_msg());
+        let forged = Evidence::new(a.clone(), b).unwrap();
+        assert!(pool.submit(forged.clone(), 0, &toy_verify).is_err());
+        assert!(pool.apply_included(&[forged], &toy_verify, &mut em, &mut reg, &mut ts, tp, 5000, 0).is_empty());
+
+        // prawdziwy nagłówek z innego slotu przepisany na slot 7
+        let mut moved = signed(who, 8, 3);
+        moved.proposal.slot = 7;
+        let relabelled = Evidence::new(a, moved).unwrap();
+        assert!(!relabelled.verify(&toy_verify));
+        assert!(pool.apply_included(&[relabelled], &toy_verify, &mut em, &mut reg, &mut ts, tp, 5000, 0).is_empty());
+        assert_eq!(reg.stake(&who), 1000);
+        assert_eq!(ts.get(&who, 0), ONE_Q);
+    }
+
+    #[test]
+    fn evidence_from_another_network_does_not_slash() {
+        let who = nid(1);
+        let (tp, mut reg, mut ts, mut em) = setup(who);
+        let mut pool = EvidencePool::new(1, 2, 10).unwrap();
+
+        // ten sam klucz, dwa nagłówki podpisane w sieci 2
+        let foreign = Evidence::new(signed_on(2, who, 7, 2), signed_on(2, who, 7, 1)).unwrap();
+        assert!(foreign.verify(&toy_verify));
+        assert_eq!(pool.submit(foreign.clone(), 0, &toy_verify), Err("evidence from another network"));
+        assert!(pool.apply_included(std::slice::from_ref(&foreign), &toy_verify, &mut em, &mut reg, &mut ts, tp, 5000, 0).is_empty());
+        assert_eq!(reg.stake(&who), 1000);
+
+        // podpis z sieci 2 nie przechodzi po przepisaniu na sieć 1
+        let mut relabelled = foreign.clone();
+        relabelled.a.network_id = 1;
+        relabelled.b.network_id = 1;
+        assert!(!relabelled.verify(&toy_verify));
+        assert_ne!(relabelled.id(), foreign.id());
+        assert!(Evidence::new(signed_on(1, who, 7, 2), signed_on(2, who, 7, 1)).is_none());
+    }
+
+    #[test]
+    fn tracker_detects_gossip_conflicts_once() {
+        let who = nid(3);
+        let mut tr = ProposalTracker::new(10);
+        let (a, b) = conflicting(who, 5);
+        assert!(tr.observe(a.clone()).is_none());
+        assert!(tr.observe(a.clone()).is_none());
+        let ev = tr.observe(b.clone()).unwrap();
+        assert_eq!(ev, Evidence::new(a.clone(), b.clone()).unwrap());
+        assert!(tr.observe(signed(who, 5, 3)).is_none());
+        assert_eq!(tr.detected().len(), 1);
+        // inny slot tego samego walidatora to nie konflikt
+        assert!(tr.observe(signed(who, 6, 1)).is_none());
+
+        let mut pool = EvidencePool::new(1, 2, 10).unwrap();
+        assert_eq!(pool.submit(ev, 0, &toy_verify), Ok(true));
+        tr.prune(16);
+        assert!(tr.detected().is_empty());
+        assert!(tr.observe(a).is_none());
+    }
+}
@@ -0,0 +1,266 @@
+//! Weighted checkpoint finality on top of PoT fork choice
+//! Every `interval_slots` validators vote for a checkpoint: the latest block at
+//! or before the checkpoint slot (many slots have no leader). Once voters
+//! holding more than 2/3 of the snapshot weight agree, the block is finalized
+//! in `ForkChoice` and reorgs below it are refused.
+//! Vote signatures (Falcon) are checked by the caller over `FinalityVote::signing_hash`;
+//! the gadget keeps them so two votes for one checkpoint become `DoubleVote` evidence.
+
+use std::collections::BTreeMap;
+
+use crate::crypto_kmac_consensus::kmac256_hash;
+use crate::evidence::HeaderVerifier;
+use crate::fork_choice::{BlockId, ForkChoice, HeadChange};
+use crate::pot::{EpochSnapshot, NodeId};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FinalityVote {
+    pub network_id: u32,
+    pub who: NodeId,
+    pub epoch: u64,
+    pub slot: u64,
+    /// `ForkChoice::ancestor_at_slot(head, slot)` of the voter
+    pub block: BlockId,
+}
+
+impl FinalityVote {
+    pub fn signing_hash(&self) -> [u8; 32] {
+        kmac256_hash(b"FIN.vote.v1", &[
+            &self.network_id.to_le_bytes(),
+            &self.who,
+            &self.epoch.to_le_bytes(),
+            &self.slot.to_le_bytes(),
+            &self.block,
+        ])
+    }
+}
+
+/// Two signed votes of one validator for different blocks at one checkpoint
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DoubleVote {
+    pub a: FinalityVote,
+    pub sig_a: Vec<u8>,
+    pub b: FinalityVote,
+    pub sig_b: Vec<u8>,
+}
+
+impl DoubleVote {
+    #[inline]
+    pub fn offender(&self) -> NodeId { self.a.who }
+
+    /// Same network, voter and slot, different blocks, both signatures valid
+    pub fn verify(&self, v: &impl HeaderVerifier) -> bool {
+        let (a, b) = (&self.a, &self.b);
+        a.network_id == b.network_id && a.who == b.who && a.slot == b.slot && a.block != b.block
+            && v.verify(&a.who, &a.signing_hash(), &self.sig_a)
+            && v.verify(&b.who, &b.signing_hash(), &self.sig_b)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub slot: u64,
+    pub block: BlockId,
+}
+
+/// voter -> (epoch, signature) of its vote
+type Voters = BTreeMap<NodeId, (u64, Vec<u8>)>;
+
+pub struct FinalityGadget {
+    pub network_id: u32,
+    pub interval_slots: u64,
+    /// checkpoint slot -> block -> voters
+    votes: BTreeMap<u64, BTreeMap<BlockId, Voters>>,
+    finalized: Option<Checkpoint>,
+    double_votes: Vec<DoubleVote>,
+}
+
+impl FinalityGadget {
+    pub fn new(network_id: u32, interval_slots: u64) -> Result<Self, &'static str> {
+        if interval_slots == 0 { return Err("interval_slots must be > 0"); }
+        Ok(Self { network_id, interval_slots, votes: BTreeMap::new(), finalized: None, double_votes: Vec::new() })
+    }
+
+    #[inline]
+    pub fn finalized(&self) -> Option<Checkpoint> { self.finalized }
+
+    #[inline]
+    pub fn is_checkpoint_slot(&self, slot: u64) -> bool { slot.checked_rem(self.interval_slots) == Some(0) }
+
+    /// Double votes seen so far, to be turned into slashing by the caller
+    pub fn take_double_votes(&mut self) -> Vec<DoubleVote> {
+        std::mem::take(&mut self.double_votes)
+    }
+
+    /// Process a vote whose signature `sig` the caller has checked. Returns the
+    /// head change caused by finalization (None while the checkpoint is below 2/3).
+    pub fn on_vote(
+        &mut self,
+        v: FinalityVote,
+        sig: &[u8],
+        snap: &EpochSnapshot,
+        fc: &mut ForkChoice,
+    ) -> Result<Option<HeadChange>, &'static str> {
+        if v.network_id != self.network_id { return Err("vote from another network"); }
+        if !self.is_checkpoint_slot(v.slot) { return Err("vote is not for a checkpoint slot"); }
+        if v.epoch != snap.epoch { return Err("vote epoch does not match snapshot"); }
+        if self.finalized.map(|c| v.slot <= c.slot).unwrap_or(false) {
+            return Err("checkpoint at or below finalized slot");
+        }
+        if snap.weight_q_of(&v.who) == 0 { return Err("voter has no weight in snapshot"); }
+        match fc.slot(&v.block) {
+            Some(s) if s <= v.slot => {}
+            _ => return Err("unknown checkpoint block for slot"),
+        }
+        if !fc.descends_from(&v.block, &fc.finalized()) {
+            return Err("checkpoint block conflicts with finalized block");
+        }
+
+        let per_slot = self.votes.entry(v.slot).or_default();
+        let earlier = per_slot.iter()
+            .find(|(b, voters)| **b != v.block && voters.contains_key(&v.who))
+            .map(|(b, voters)| (*b, voters[&v.who].clone()));
+        if let Some((block, (epoch, sig_a))) = earlier {
+            // zapamiętujemy tylko pierwszy konflikt na (who, slot)
+            if !self.double_votes.iter().any(|d| d.offender() == v.who && d.a.slot == v.slot) {
+                let a = FinalityVote { network_id: self.network_id, who: v.who, epoch, slot: v.slot, block };
+                self.double_votes.push(DoubleVote { a, sig_a, b: v, sig_b: sig.to_vec() });
+            }
+            return Err("conflicting finality vote");
+        }
+        let voters = per_slot.entry(v.block).or_default();
+        voters.entry(v.who).or_insert_with(|| (v.epoch, sig.to_vec()));
+
+        let tally = voters.keys().fold(0u64, |acc, w| acc.saturating_add(snap.weight_q_of(w)));
+        if u128::from(tally) * 3 <= u128::from(snap.sum_weights_q) * 2 { return Ok(None); }
+
+        let change = fc.finalize(v.block)?;
+        self.finalized = Some(Checkpoint { slot: v.slot, block: v.block });
+        self.votes = match v.slot.checked_add(1) {
+            Some(next) => self.votes.split_off(&next),
+            None => BTreeMap::new(),
+        };
+        Ok(Some(change))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pot::{Registry, TrustParams, TrustState, ONE_Q};
+
+    fn nid(n: u8) -> NodeId {
+        let mut id = [0u8; 32];
+        id[0] = n;
+        id
+    }
+
+    #[test]
+    fn two_thirds_weight_finalizes() {
+        let tp = TrustParams { alpha_q: ONE_Q, beta_q: 0, init_q: ONE_Q };
+        let mut reg = Registry::default();
+        for n in 1..=4 { reg.insert(nid(n), 1000, true); }
+        let snap = EpochSnapshot::build(0, &reg, &TrustState::default(), &tp, 0);
+
+        let g = [0u8; 32];
+        let (b1, b2) = ([1u8; 32], [2u8; 32]);
+        let mut fc = ForkChoice::new(g);
+        fc.insert(b1, g, 4, 10).unwrap();
+        fc.insert(b2, g, 4, 5).unwrap();
+        let mut fin = FinalityGadget::new(1, 4).unwrap();
+        let vote = |n: u8, block| FinalityVote { network_id: 1, who: nid(n), epoch: 0, slot: 4, block };
+
+        assert_eq!(fin.on_vote(vote(1, b2), &[], &snap, &mut fc), Ok(None));
+        assert!(fin.on_vote(vote(1, b1), &[], &snap, &mut fc).is_err());
+        assert!(fin.on_vote(FinalityVote { network_id: 2, ..vote(2, b2) }, &[], &snap, &mut fc).is_err());
+        assert_eq!(fin.on_vote(vote(2, b2), &[], &snap, &mut fc), Ok(None));
+        assert!(fin.on_vote(FinalityVote { slot: 5, ..vote(3, b2) }, &[], &snap, &mut fc).is_err());
+        // 3/4 > 2/3
+        assert!(matches!(fin.on_vote(vote(3, b2), &[], &snap, &mut fc), Ok(Some(HeadChange::Reorg(_)))));
+        assert_eq!(fin.finalized(), Some(Checkpoint { slot: 4, block: b2 }));
+        assert_eq!(fc.head(), b2);
+        assert!(fin.on_vote(vote(4, b2), &[], &snap, &mut fc).is_err());
+        assert_ne!(vote(1, b1).signing_hash(), vote(1, b2).signing_hash());
+    }
+
+    // zabawkowy podpis: w węźle jest to Falcon z kluczem walidatora
+    fn toy_sig(v: &FinalityVote) -> Vec<u8> {
+        kmac256_hash(b"TEST.sig", &[&v.who, &v.signing_hash()]).to_vec()
+    }
+
+    fn toy_verify(who: &NodeId, msg: &[u8; 32], sig: &[u8]) -> bool {
+        kmac256_hash(b"TEST.sig", &[who, msg]).as_slice() == sig
+    }
+
+    #[test]
+    fn double_vote_is_kept_as_evidence() {
+        let tp = TrustParams { alpha_q: ONE_Q, beta_q: 0, init_q: ONE_Q };
+        let mut reg = Registry::default();
+        for n in 1..=4 { reg.insert(nid(n), 1000, true); }
+        let snap = EpochSnapshot::build(0, &reg, &TrustState::default(), &tp, 0);
+
+        let g = [0u8; 32];
+        let (b1, b2) = ([1u8; 32], [2u8; 32]);
+        let mut fc = ForkChoice::new(g);
+        fc.insert(b1, g, 4, 10).unwrap();
+        fc.insert(b2, g, 4, 5).unwrap();
+        let mut fin = FinalityGadget::new(1, 4).unwrap();
+        let vote = |block| FinalityVote { network_id: 1, who: nid(1), epoch: 0, slot: 4, block };
+
+        assert_eq!(fin.on_vote(vote(b1), &toy_sig(&vote(b1)), &snap, &mut fc), Ok(None));
+        assert!(fin.on_vote(vote(b2), &toy_sig(&vote(b2)), &snap, &mut fc).is_err());
+        assert!(fin.on_vote(vote(b2), &toy_sig(&vote(b2)), &snap, &mut fc).is_err());
+        let dv = fin.take_double_votes();
+        assert_eq!(dv.len(), 1);
+        assert_eq!(dv[0].offender(), nid(1));
+        assert!(dv[0].verify(&toy_verify));
+        assert!(fin.take_double_votes().is_empty());
+
+        let mut forged = dv[0].clone();
+        forged.b.block = b1;
+        assert!(!forged.verify(&toy_verify));
+    }
+
+    #[test]
+    fn finalizing_last_slot_does_not_overflow() {
+        let tp = TrustParams { alpha_q: ONE_Q, beta_q: 0, init_q: ONE_Q };
+        let mut reg = Registry::default();
+        reg.insert(nid(1), 1000, true);
+        let snap = EpochSnapshot::build(0, &reg, &TrustState::default(), &tp, 0);
+
+        let g = [0u8; 32];
+        let b = [1u8; 32];
+        let mut fc = ForkChoice::new(g);
+        fc.insert(b, g, u64::MAX, 10).unwrap();
+        let mut fin = FinalityGadget::new(0, 1).unwrap();
+        let v = FinalityVote { network_id: 0, who: nid(1), epoch: 0, slot: u64::MAX, block: b };
+        assert!(fin.on_vote(v, &[], &snap, &mut fc).unwrap().is_some());
+        assert_eq!(fin.finalized(), Some(Checkpoint { slot: u64::MAX, block: b }));
+    }
+
+    #[test]
+    fn empty_checkpoint_slot_votes_for_latest_ancestor() {
+        let tp = TrustParams { alpha_q: ONE_Q, beta_q: 0, init_q: ONE_Q };
+        let mut reg = Registry::default();
+        for n in 1..=3 { reg.insert(nid(n), 1000, true); }
+        let snap = EpochSnapshot::build(0, &reg, &TrustState::default(), &tp, 0);
+
+        // slot 8 bez lidera: ostatni blok przed nim jest w slocie 6
+        let g = [0u8; 32];
+        let (b6, b9) = ([6u8; 32], [9u8; 32]);
+        let mut fc = ForkChoice::new(g);
+        fc.insert(b6, g, 6, 10).unwrap();
+        fc.insert(b9, b6, 9, 10).unwrap();
+        assert_eq!(fc.ancestor_at_slot(&b9, 8), Some(b6));
+        assert_eq!(fc.ancestor_at_slot(&b9, 9), Some(b9));
+
+        let mut fin = FinalityGadget::new(0, 4).unwrap();
+        let vote = |n: u8, block| FinalityVote { network_id: 0, who: nid(n), epoch: 0, slot: 8, block };
+        assert!(fin.on_vote(vote(1, b9), &[], &snap, &mut fc).is_err());
+        assert_eq!(fin.on_vote(vote(1, b6), &[], &snap, &mut fc), Ok(None));
+        assert_eq!(fin.on_vote(vote(2, b6), &[], &snap, &mut fc), Ok(None));
+        assert_eq!(fin.on_vote(vote(3, b6), &[], &snap, &mut fc), Ok(Some(HeadChange::Unchanged)));
+        assert_eq!(fin.finalized(), Some(Checkpoint { slot: 8, block: b6 }));
+        assert_eq!(fc.finalized(), b6);
+    }
+}
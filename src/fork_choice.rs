@@ -0,0 +1,492 @@
+//! Fork choice based on cumulative PoT weight
+//! Uses the u128 sortition weights returned by `verify_leader_*` (no floats),
+//! ties broken by the lower block id so every node picks the same head.
+//! Blocks that do not descend from the finalized checkpoint are rejected, and
+//! finalization prunes every stored block that is not its descendant.
+//! Blocks whose parent is not known yet wait in a bounded `OrphanPool`.
+
+use std::collections::{BTreeMap, HashMap};
+
+pub type BlockId = [u8; 32];
+
+#[derive(Clone, Debug)]
+struct BlockMeta {
+    parent: BlockId,
+    height: u64,
+    slot: u64,
+    cum_weight: u128,
+}
+
+/// Head switch to a block that does not extend the previous head.
+/// Callers roll back `rolled_back` (newest first), then apply `applied` (oldest first).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReorgEvent {
+    pub old_head: BlockId,
+    pub new_head: BlockId,
+    pub common_ancestor: BlockId,
+    pub rolled_back: Vec<BlockId>,
+    pub applied: Vec<BlockId>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HeadChange {
+    /// Block stored on a side branch, head unchanged
+    Unchanged,
+    /// Block extends the current head
+    Extended(BlockId),
+    Reorg(ReorgEvent),
+}
+
+pub struct ForkChoice {
+    blocks: HashMap<BlockId, BlockMeta>,
+    /// height -> blocks at that height (for pruning on finalization)
+    by_height: BTreeMap<u64, Vec<BlockId>>,
+    genesis: BlockId,
+    head: BlockId,
+    finalized: BlockId,
+}
+
+impl ForkChoice {
+    pub fn new(genesis: BlockId) -> Self {
+        let mut blocks = HashMap::new();
+        blocks.insert(genesis, BlockMeta { parent: genesis, height: 0, slot: 0, cum_weight: 0 });
+        let by_height = BTreeMap::from([(0, vec![genesis])]);
+        Self { blocks, by_height, genesis, head: genesis, finalized: genesis }
+    }
+
+    #[inline]
+    pub fn head(&self) -> BlockId { self.head }
+
+    #[inline]
+    pub fn genesis(&self) -> BlockId { self.genesis }
+
+    #[inline]
+    pub fn finalized(&self) -> BlockId { self.finalized }
+
+    #[inline]
+    pub fn finalized_height(&self) -> u64 { self.blocks[&self.finalized].height }
+
+    #[inline]
+    pub fn contains(&self, id: &BlockId) -> bool { self.blocks.contains_key(id) }
+
+    /// Number of stored blocks (finalized block and its unfinalized descendants)
+    #[inline]
+    pub fn len(&self) -> usize { self.blocks.len() }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.blocks.is_empty() }
+
+    #[inline]
+    pub fn slot(&self, id: &BlockId) -> Option<u64> {
+        self.blocks.get(id).map(|m| m.slot)
+    }
+
+    #[inline]
+    pub fn height(&self, id: &BlockId) -> Option<u64> {
+        self.blocks.get(id).map(|m| m.height)
+    }
+
+    #[inline]
+    pub fn cumulative_weight(&self, id: &BlockId) -> Option<u128> {
+        self.blocks.get(id).map(|m| m.cum_weight)
+    }
+
+    #[inline]
+    pub fn parent(&self, id: &BlockId) -> Option<BlockId> {
+        if *id == self.genesis { return None; }
+        self.blocks.get(id).map(|m| m.parent)
+    }
+
+    /// Insert a verified block with its sortition `weight` and re-run fork choice
+    pub fn insert(&mut self, id: BlockId, parent: BlockId, slot: u64, weight: u128) -> Result<HeadChange, &'static str> {
+        if self.blocks.contains_key(&id) { return Err("block already known"); }
+        let p = self.blocks.get(&parent).ok_or("unknown parent")?;
+        if !self.descends_from(&parent, &self.finalized) {
+            return Err("block conflicts with finalized checkpoint");
+        }
+        if parent != self.genesis && slot <= p.slot {
+            return Err("block slot must be greater than parent slot");
+        }
+        let meta = BlockMeta {
+            parent,
+            height: p.height + 1,
+            slot,
+            cum_weight: p.cum_weight.saturating_add(weight),
+        };
+        self.by_height.entry(meta.height).or_default().push(id);
+        self.blocks.insert(id, meta);
+
+        if !self.is_better(&id, &self.head) {
+            return Ok(HeadChange::Unchanged);
+        }
+        let old = self.head;
+        self.head = id;
+        if parent == old {
+            return Ok(HeadChange::Extended(id));
+        }
+        Ok(HeadChange::Reorg(self.reorg_path(old, id)))
+    }
+
+    /// true if `id` == `ancestor` or `ancestor` is on the path from `id` to genesis
+    pub fn descends_from(&self, id: &BlockId, ancestor: &BlockId) -> bool {
+        let (Some(mut cur), Some(anc)) = (self.blocks.get(id), self.blocks.get(ancestor)) else {
+            return false;
+        };
+        let mut cur_id = *id;
+        while cur.height > anc.height {
+            cur_id = cur.parent;
+            cur = &self.blocks[&cur_id];
+        }
+        cur_id == *ancestor
+    }
+
+    /// Latest block at or before `slot` on the chain ending at `tip`
+    /// (the checkpoint target when no leader proposed in `slot` itself)
+    pub fn ancestor_at_slot(&self, tip: &BlockId, slot: u64) -> Option<BlockId> {
+        let mut id = *tip;
+        loop {
+            if self.blocks.get(&id)?.slot <= slot { return Some(id); }
+            id = self.parent(&id)?;
+        }
+    }
+
+    /// Mark `id` final. It must extend the previous finalized block. Blocks
+    /// that do not descend from `id` (its ancestors included) are pruned. If
+    /// the current head was on another branch, the head moves to the best
+    /// remaining block (reported as a reorg).
+    pub fn finalize(&mut self, id: BlockId) -> Result<HeadChange, &'static str> {
+        if !self.blocks.contains_key(&id) { return Err("unknown block"); }
+        if !self.descends_from(&id, &self.finalized) {
+            return Err("finalized block must extend the previous finalized block");
+        }
+        self.finalized = id;
+        let head_survives = self.descends_from(&self.head, &id);
+        let old = self.head;
+        let pruned = self.prune_to_finalized();
+        if head_survives { return Ok(HeadChange::Unchanged); }
+
+        // po przycięciu zostają tylko potomkowie `id`
+        let mut best = id;
+        for cand in self.blocks.keys() {
+            if self.is_better(cand, &best) { best = *cand; }
+        }
+        self.head = best;
+        Ok(HeadChange::Reorg(self.reorg_path_with(old, best, &pruned)))
+    }
+
+    /// Keep only the finalized block and its descendants; returns the pruned
+    /// blocks (with their metadata) so a reorg away from them can still be reported
+    fn prune_to_finalized(&mut self) -> HashMap<BlockId, BlockMeta> {
+        let fin = self.finalized;
+        let fin_height = self.blocks[&fin].height;
+        let above = self.by_height.split_off(&(fin_height + 1));
+        let below = std::mem::replace(&mut self.by_height, above);
+        let mut pruned = HashMap::new();
+        for id in below.into_values().flatten().filter(|b| *b != fin) {
+            if let Some(m) = self.blocks.remove(&id) { pruned.insert(id, m); }
+        }
+        self.by_height.insert(fin_height, vec![fin]);
+
+        // rosnąco po wysokości: rodzic jest rozstrzygnięty przed dzieckiem
+        let blocks = &mut self.blocks;
+        for ids in self.by_height.range_mut(fin_height + 1..).map(|(_, v)| v) {
+            ids.retain(|b| {
+                let keep = blocks.contains_key(&blocks[b].parent);
+                if !keep { if let Some(m) = blocks.remove(b) { pruned.insert(*b, m); } }
+                keep
+            });
+        }
+        self.by_height.retain(|_, v| !v.is_empty());
+        pruned
+    }
+
+    /// Deterministic order: higher cumulative weight, then lower id
+    fn is_better(&self, a: &BlockId, b: &BlockId) -> bool {
+        let (wa, wb) = (self.blocks[a].cum_weight, self.blocks[b].cum_weight);
+        wa > wb || (wa == wb && a < b)
+    }
+
+    fn reorg_path(&self, old_head: BlockId, new_head: BlockId) -> ReorgEvent {
+        self.reorg_path_with(old_head, new_head, &HashMap::new())
+    }
+
+    /// `pruned` holds blocks just dropped by finalization (the old head's branch)
+    fn reorg_path_with(&self, old_head: BlockId, new_head: BlockId, pruned: &HashMap<BlockId, BlockMeta>) -> ReorgEvent {
+        let meta = |id: &BlockId| self.blocks.get(id).or_else(|| pruned.get(id)).expect("block on reorg path");
+        let mut a = old_head;
+        let mut b = new_head;
+        let mut rolled_back = Vec::new();
+        let mut applied = Vec::new();
+
+        while meta(&a).height > meta(&b).height {
+            rolled_back.push(a);
+            a = meta(&a).parent;
+        }
+        while meta(&b).height > meta(&a).height {
+            applied.push(b);
+            b = meta(&b).parent;
+        }
+        while a != b {
+            rolled_back.push(a);
+            applied.push(b);
+            a = meta(&a).parent;
+            b = meta(&b).parent;
+        }
+        applied.reverse();
+        ReorgEvent { old_head, new_head, common_ancestor: a, rolled_back, applied }
+    }
+}
+
+struct Orphan<T> {
+    parent: BlockId,
+    size: usize,
+    seq: u64,
+    item: T,
+}
+
+/// Blocks waiting for a missing parent, indexed by that parent so all children
+/// can be replayed once it arrives. Bounded by count and bytes; the oldest
+/// orphans are evicted first.
+pub struct OrphanPool<T> {
+    orphans: HashMap<BlockId, Orphan<T>>,
+    by_parent: HashMap<BlockId, Vec<BlockId>>,
+    /// seq -> id, insertion order; entries leave together with their orphan
+    order: BTreeMap<u64, BlockId>,
+    next_seq: u64,
+    bytes: usize,
+    max_count: usize,
+    max_bytes: usize,
+}
+
+impl<T> OrphanPool<T> {
+    pub fn new(max_count: usize, max_bytes: usize) -> Self {
+        Self {
+            orphans: HashMap::new(),
+            by_parent: HashMap::new(),
+            order: BTreeMap::new(),
+            next_seq: 0,
+            bytes: 0,
+            max_count,
+            max_bytes,
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize { self.orphans.len() }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.orphans.is_empty() }
+
+    #[inline]
+    pub fn bytes(&self) -> usize { self.bytes }
+
+    #[inline]
+    pub fn contains(&self, id: &BlockId) -> bool { self.orphans.contains_key(id) }
+
+    /// Store an orphan of `size` bytes. Returns the parent to request from peers
+    /// when nothing was waiting on it before and it is not an orphan itself.
+    pub fn insert(&mut self, id: BlockId, parent: BlockId, size: usize, item: T) -> Result<Option<BlockId>, &'static str> {
+        if self.orphans.contains_key(&id) { return Err("orphan already known"); }
+        if self.max_count == 0 { return Err("orphan pool disabled (max_count = 0)"); }
+        if size > self.max_bytes { return Err("orphan too large"); }
+        while self.orphans.len() >= self.max_count || self.bytes + size > self.max_bytes {
+            self.evict_oldest();
+        }
+
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.order.insert(seq, id);
+        self.bytes += size;
+        self.orphans.insert(id, Orphan { parent, size, seq, item });
+
+        let children = self.by_parent.entry(parent).or_default();
+        children.push(id);
+        let request = children.len() == 1 && !self.orphans.contains_key(&parent);
+        Ok(request.then_some(parent))
+    }
+
+    /// Remove and return (oldest first) all orphans waiting on `parent`.
+    /// Callers insert them and call this again for each accepted child.
+    pub fn take_children(&mut self, parent: &BlockId) -> Vec<(BlockId, T)> {
+        let Some(ids) = self.by_parent.remove(parent) else { return Vec::new() };
+        ids.into_iter()
+            .filter_map(|id| {
+                let o = self.orphans.remove(&id)?;
+                self.order.remove(&o.seq);
+                self.bytes -= o.size;
+                Some((id, o.item))
+            })
+            .collect()
+    }
+
+    /// Missing parents that are not orphans themselves (roots to request)
+    pub fn missing_parents(&self) -> Vec<BlockId> {
+        let mut out: Vec<BlockId> = self.by_parent.keys()
+            .filter(|p| !self.orphans.contains_key(*p))
+            .copied()
+            .collect();
+        out.sort();
+        out
+    }
+
+    fn evict_oldest(&mut self) {
+        let Some((_, id)) = self.order.pop_first() else { return };
+        let Some(o) = self.orphans.remove(&id) else { return };
+        self.bytes -= o.size;
+        if let Some(children) = self.by_parent.get_mut(&o.parent) {
+            children.retain(|c| *c != id);
+            if children.is_empty() { self.by_parent.remove(&o.parent); }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bid(n: u8) -> BlockId {
+        let mut id = [0u8; 32];
+        id[0] = n;
+        id
+    }
+
+    #[test]
+    fn extends_and_reorgs_by_weight() {
+        let g = bid(0);
+        let mut fc = ForkChoice::new(g);
+        assert_eq!(fc.insert(bid(1), g, 1, 100).unwrap(), HeadChange::Extended(bid(1)));
+        assert_eq!(fc.insert(bid(2), bid(1), 2, 100).unwrap(), HeadChange::Extended(bid(2)));
+
+        // gałąź boczna z mniejszą wagą
+        assert_eq!(fc.insert(bid(3), bid(1), 2, 50).unwrap(), HeadChange::Unchanged);
+        assert_eq!(fc.head(), bid(2));
+
+        // gałąź boczna przegania
+        match fc.insert(bid(4), bid(3), 3, 60).unwrap() {
+            HeadChange::Reorg(ev) => {
+                assert_eq!(ev.old_head, bid(2));
+                assert_eq!(ev.new_head, bid(4));
+                assert_eq!(ev.common_ancestor, bid(1));
+                assert_eq!(ev.rolled_back, vec![bid(2)]);
+                assert_eq!(ev.applied, vec![bid(3), bid(4)]);
+            }
+            other => panic!("expected reorg, got {other:?}"),
+        }
+        assert_eq!(fc.cumulative_weight(&bid(4)), Some(210));
+    }
+
+    #[test]
+    fn tie_break_is_deterministic() {
+        let g = bid(0);
+        let mut fc = ForkChoice::new(g);
+        fc.insert(bid(9), g, 1, 100).unwrap();
+        // ta sama waga, mniejszy id wygrywa
+        assert!(matches!(fc.insert(bid(5), g, 1, 100).unwrap(), HeadChange::Reorg(_)));
+        assert_eq!(fc.head(), bid(5));
+        assert_eq!(fc.insert(bid(7), g, 1, 100).unwrap(), HeadChange::Unchanged);
+    }
+
+    #[test]
+    fn rejects_invalid_inserts() {
+        let g = bid(0);
+        let mut fc = ForkChoice::new(g);
+        fc.insert(bid(1), g, 5, 1).unwrap();
+        assert!(fc.insert(bid(1), g, 5, 1).is_err());
+        assert!(fc.insert(bid(2), bid(42), 6, 1).is_err());
+        assert!(fc.insert(bid(3), bid(1), 5, 1).is_err());
+    }
+
+    #[test]
+    fn finality_blocks_conflicting_branches() {
+        let g = bid(0);
+        let mut fc = ForkChoice::new(g);
+        fc.insert(bid(1), g, 1, 100).unwrap();
+        fc.insert(bid(2), bid(1), 2, 100).unwrap();
+        fc.insert(bid(3), g, 1, 50).unwrap();
+        fc.insert(bid(4), bid(3), 2, 50).unwrap();
+        assert_eq!(fc.head(), bid(2));
+
+        // finalizacja bocznej gałęzi przenosi head
+        match fc.finalize(bid(3)).unwrap() {
+            HeadChange::Reorg(ev) => {
+                assert_eq!(ev.new_head, bid(4));
+                // porzucona gałąź jest już przycięta, ale nadal do wycofania
+                assert_eq!(ev.rolled_back, vec![bid(2), bid(1)]);
+                assert_eq!(ev.applied, vec![bid(3), bid(4)]);
+                assert_eq!(ev.common_ancestor, g);
+            }
+            other => panic!("expected reorg, got {other:?}"),
+        }
+        assert_eq!(fc.finalized_height(), 1);
+        assert!(fc.insert(bid(5), bid(2), 3, 1000).is_err());
+        assert!(fc.finalize(bid(1)).is_err());
+        assert_eq!(fc.insert(bid(6), bid(4), 3, 1).unwrap(), HeadChange::Extended(bid(6)));
+        assert!(fc.descends_from(&bid(6), &bid(3)));
+        assert!(!fc.descends_from(&bid(2), &bid(3)));
+    }
+
+    #[test]
+    fn finalize_prunes_non_descendants() {
+        let g = bid(0);
+        let mut fc = ForkChoice::new(g);
+        fc.insert(bid(1), g, 1, 100).unwrap();
+        fc.insert(bid(2), bid(1), 2, 100).unwrap();
+        fc.insert(bid(3), bid(2), 3, 100).unwrap();
+        fc.insert(bid(4), bid(1), 2, 10).unwrap();
+        fc.insert(bid(5), bid(4), 3, 10).unwrap();
+        fc.insert(bid(6), bid(2), 3, 10).unwrap();
+        assert_eq!(fc.len(), 7);
+
+        assert_eq!(fc.finalize(bid(2)).unwrap(), HeadChange::Unchanged);
+        // zostaje finalizowany blok i jego potomkowie
+        assert_eq!(fc.len(), 3);
+        for gone in [g, bid(1), bid(4), bid(5)] { assert!(!fc.contains(&gone)); }
+        assert_eq!(fc.head(), bid(3));
+        assert_eq!(fc.finalized_height(), 2);
+        assert_eq!(fc.by_height.values().map(Vec::len).sum::<usize>(), fc.len());
+        assert_eq!(fc.ancestor_at_slot(&bid(3), 1), None);
+        assert_eq!(fc.ancestor_at_slot(&bid(3), 2), Some(bid(2)));
+
+        assert_eq!(fc.insert(bid(7), bid(3), 4, 1).unwrap(), HeadChange::Extended(bid(7)));
+        assert_eq!(fc.finalize(bid(7)).unwrap(), HeadChange::Unchanged);
+        assert_eq!(fc.len(), 1);
+        assert_eq!(fc.by_height.len(), 1);
+    }
+
+    #[test]
+    fn orphan_pool_indexes_by_parent_and_evicts() {
+        let mut pool: OrphanPool<u8> = OrphanPool::new(3, 100);
+        // dwa dzieci tego samego brakującego rodzica, request tylko raz
+        assert_eq!(pool.insert(bid(11), bid(10), 10, 1).unwrap(), Some(bid(10)));
+        assert_eq!(pool.insert(bid(12), bid(10), 10, 2).unwrap(), None);
+        // wnuk czeka na sieroty, nie trzeba go żądać
+        assert_eq!(pool.insert(bid(13), bid(11), 10, 3).unwrap(), None);
+        assert!(pool.insert(bid(13), bid(11), 10, 3).is_err());
+        assert_eq!(pool.missing_parents(), vec![bid(10)]);
+
+        let kids = pool.take_children(&bid(10));
+        assert_eq!(kids, vec![(bid(11), 1), (bid(12), 2)]);
+        assert_eq!(pool.take_children(&bid(11)), vec![(bid(13), 3)]);
+        assert!(pool.is_empty());
+        assert_eq!(pool.bytes(), 0);
+        // kolejność nie trzyma wpisów po zabranych dzieciach
+        assert!(pool.order.is_empty());
+
+        // limit liczby: najstarszy wylatuje
+        for n in 20..24u8 {
+            pool.insert(bid(n), bid(n + 100), 10, n).unwrap();
+        }
+        assert_eq!(pool.len(), 3);
+        assert!(!pool.contains(&bid(20)));
+        assert!(pool.take_children(&bid(120)).is_empty());
+
+        // limit bajtów
+        pool.insert(bid(30), bid(130), 95, 0).unwrap();
+        assert_eq!(pool.len(), 1);
+        assert!(pool.insert(bid(31), bid(131), 101, 0).is_err());
+        assert_eq!(pool.order.len(), pool.len());
+
+        let mut off: OrphanPool<u8> = OrphanPool::new(0, 100);
+        assert_eq!(off.insert(bid(40), bid(140), 1, 0), Err("orphan pool disabled (max_count = 0)"));
+    }
+}
@@ -0,0 +1,348 @@
+//! Governance of consensus parameters
+//! Parameter-change proposals are voted on with snapshot weight (stake_q * trust_q).
+//! Once voters holding `threshold_bps` of Σweights agree, the change is scheduled
+//! for the next epoch boundary. Proposals and votes are signed over payloads
+//! bound to the network id; `Governance` is serde so it survives a restart.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::crypto_kmac_consensus::kmac256_hash;
+use crate::evidence::HeaderVerifier;
+use crate::pot::{EpochSnapshot, NodeId, PotParams, TrustParams, Q};
+
+pub type ProposalId = [u8; 32];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ParamChange {
+    TrustAlpha(Q),
+    TrustBeta(Q),
+    TrustInit(Q),
+    Lambda(Q),
+    MinBond(u64),
+    SlashNorevealBps(u32),
+}
+
+impl ParamChange {
+    fn encode(&self) -> [u8; 9] {
+        let (tag, v) = match *self {
+            ParamChange::TrustAlpha(q) => (1u8, q),
+            ParamChange::TrustBeta(q) => (2, q),
+            ParamChange::TrustInit(q) => (3, q),
+            ParamChange::Lambda(q) => (4, q),
+            ParamChange::MinBond(b) => (5, b),
+            ParamChange::SlashNorevealBps(bps) => (6, u64::from(bps)),
+        };
+        let mut out = [0u8; 9];
+        out[0] = tag;
+        out[1..].copy_from_slice(&v.to_le_bytes());
+        out
+    }
+
+    pub fn apply(&self, p: &mut PotParams) -> Result<(), &'static str> {
+        let t = p.trust;
+        match *self {
+            ParamChange::TrustAlpha(q) => p.trust = TrustParams::new(q, t.beta_q, t.init_q)?,
+            ParamChange::TrustBeta(q) => p.trust = TrustParams::new(t.alpha_q, q, t.init_q)?,
+            ParamChange::TrustInit(q) => p.trust = TrustParams::new(t.alpha_q, t.beta_q, q)?,
+            ParamChange::Lambda(q) => {
+                if q == 0 { return Err("lambda must be > 0"); }
+                p.lambda_q = q;
+            }
+            ParamChange::MinBond(b) => p.min_bond = b,
+            ParamChange::SlashNorevealBps(bps) => {
+                if bps > 10_000 { return Err("slash bps must be <= 10000"); }
+                p.slash_noreveal_bps = bps;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParamProposal {
+    pub proposer: NodeId,
+    /// Epoch in which the proposal was made (also bounds its lifetime)
+    pub epoch: u64,
+    pub changes: Vec<ParamChange>,
+}
+
+impl ParamProposal {
+    pub fn id(&self) -> ProposalId {
+        let enc: Vec<u8> = self.changes.iter().flat_map(|c| c.encode()).collect();
+        kmac256_hash(b"GOV.v1", &[&self.proposer, &self.epoch.to_le_bytes(), &enc])
+    }
+
+    /// Message signed by the proposer
+    pub fn signing_hash(&self, network_id: u32) -> [u8; 32] {
+        kmac256_hash(b"GOV.propose.v1", &[&network_id.to_le_bytes(), &self.id()])
+    }
+
+    /// Result of applying all changes, in order, to `params`
+    pub fn applied_to(&self, params: &PotParams) -> Result<PotParams, &'static str> {
+        if self.changes.is_empty() { return Err("empty proposal"); }
+        let mut p = *params;
+        for c in &self.changes { c.apply(&mut p)?; }
+        Ok(p)
+    }
+}
+
+/// A validator's approval of a pending proposal
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParamVote {
+    pub network_id: u32,
+    pub id: ProposalId,
+    pub who: NodeId,
+    /// Epoch of the snapshot the vote is weighted with
+    pub epoch: u64,
+}
+
+impl ParamVote {
+    /// Message signed by the voter
+    pub fn signing_hash(&self) -> [u8; 32] {
+        kmac256_hash(b"GOV.vote.v1", &[
+            &self.network_id.to_le_bytes(),
+            &self.id,
+            &self.who,
+            &self.epoch.to_le_bytes(),
+        ])
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct Pending {
+    proposal: ParamProposal,
+    voters: BTreeSet<NodeId>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Governance {
+    pending: BTreeMap<ProposalId, Pending>,
+    /// epoka aktywacji -> przegłosowane propozycje (w kolejności przegłosowania)
+    scheduled: BTreeMap<u64, Vec<(ProposalId, ParamProposal)>>,
+    pub network_id: u32,
+    pub threshold_bps: u32,
+    pub max_age_epochs: u64,
+}
+
+impl Governance {
+    /// `threshold_bps` must be a strict majority (e.g. 6667 for 2/3)
+    pub fn new(network_id: u32, threshold_bps: u32, max_age_epochs: u64) -> Result<Self, &'static str> {
+        if !(5_001..=10_000).contains(&threshold_bps) {
+            return Err("threshold must be in 5001..=10000 bps");
+        }
+        Ok(Self { pending: BTreeMap::new(), scheduled: BTreeMap::new(), network_id, threshold_bps, max_age_epochs })
+    }
+
+    #[inline]
+    pub fn is_pending(&self, id: &ProposalId) -> bool { self.pending.contains_key(id) }
+
+    /// Activation epoch of a passed proposal
+    pub fn scheduled_epoch(&self, id: &ProposalId) -> Option<u64> {
+        self.scheduled.iter()
+            .find(|(_, v)| v.iter().any(|(pid, _)| pid == id))
+            .map(|(e, _)| *e)
+    }
+
+    /// Add a signed proposal. Only validators with weight in the current
+    /// snapshot may propose, so `pending` cannot be flooded by outsiders.
+    pub fn propose(
+        &mut self,
+        p: ParamProposal,
+        sig: &[u8],
+        verifier: &impl HeaderVerifier,
+        params: &PotParams,
+        snap: &EpochSnapshot,
+    ) -> Result<ProposalId, &'static str> {
+        if p.epoch != snap.epoch { return Err("proposal epoch must be the current epoch"); }
+        if snap.weight_q_of(&p.proposer) == 0 { return Err("proposer has no weight in snapshot"); }
+        if !verifier.verify(&p.proposer, &p.signing_hash(self.network_id), sig) {
+            return Err("invalid proposal signature");
+        }
+        p.applied_to(params)?;
+        let id = p.id();
+        if self.pending.contains_key(&id) || self.scheduled_epoch(&id).is_some() {
+            return Err("proposal already known");
+        }
+        self.pending.insert(id, Pending { proposal: p, voters: BTreeSet::new() });
+        Ok(id)
+    }
+
+    /// Σ weight of the voters of `id`, measured in `snap`
+    pub fn tally_q(&self, id: &ProposalId, snap: &EpochSnapshot) -> Option<Q> {
+        let p = self.pending.get(id)?;
+        Some(p.voters.iter().fold(0u64, |acc, w| acc.saturating_add(snap.weight_q_of(w))))
+    }
+
+    /// Record a signed vote using the current epoch snapshot.
+    /// Ok(true) once the proposal passed and was scheduled for `snap.epoch + 1`.
+    pub fn vote(
+        &mut self,
+        v: &ParamVote,
+        sig: &[u8],
+        verifier: &impl HeaderVerifier,
+        snap: &EpochSnapshot,
+    ) -> Result<bool, &'static str> {
+        if v.network_id != self.network_id { return Err("vote from another network"); }
+        if v.epoch != snap.epoch { return Err("vote epoch does not match snapshot"); }
+        let (id, who) = (&v.id, v.who);
+        if snap.weight_q_of(&who) == 0 { return Err("voter has no weight in snapshot"); }
+        if !verifier.verify(&who, &v.signing_hash(), sig) { return Err("invalid vote signature"); }
+        let p = self.pending.get_mut(id).ok_or("unknown proposal")?;
+        // migawka sprzed propozycji mogłaby ją uchwalić na stare wagi
+        if snap.epoch < p.proposal.epoch { return Err("snapshot predates proposal"); }
+        if snap.epoch.saturating_sub(p.proposal.epoch) > self.max_age_epochs {
+            return Err("proposal expired");
+        }
+        p.voters.insert(who);
+
+        let tally = self.tally_q(id, snap).unwrap_or(0);
+        let passed = u128::from(tally) * 10_000
+            >= u128::from(self.threshold_bps) * u128::from(snap.sum_weights_q);
+        if !passed || snap.sum_weights_q == 0 { return Ok(false); }
+
+        if let Some(done) = self.pending.remove(id) {
+            self.scheduled.entry(snap.epoch + 1).or_default().push((*id, done.proposal));
+        }
+        Ok(true)
+    }
+
+    /// Drop proposals that did not pass within `max_age_epochs`
+    pub fn prune(&mut self, current_epoch: u64) {
+        let max_age = self.max_age_epochs;
+        self.pending.retain(|_, p| current_epoch.saturating_sub(p.proposal.epoch) <= max_age);
+    }
+
+    /// Call at the epoch boundary before building the snapshot of `epoch`.
+    /// Applies every proposal scheduled up to `epoch`; returns the applied ids.
+    pub fn on_epoch(&mut self, epoch: u64, params: &mut PotParams) -> Vec<ProposalId> {
+        let later = self.scheduled.split_off(&(epoch + 1));
+        let due = std::mem::replace(&mut self.scheduled, later);
+        let mut applied = Vec::new();
+        for (id, p) in due.into_values().flatten() {
+            // wcześniejsza zmiana mogła unieważnić tę – wtedy pomijamy w całości
+            if let Ok(next) = p.applied_to(params) {
+                *params = next;
+                applied.push(id);
+            }
+        }
+        applied
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pot::{q_from_basis_points, Registry, TrustState, ONE_Q};
+
+    fn nid(n: u8) -> NodeId {
+        let mut id = [0u8; 32];
+        id[0] = n;
+        id
+    }
+
+    // zabawkowy podpis: w węźle jest to Falcon z kluczem walidatora
+    fn toy_sig(who: &NodeId, msg: &[u8; 32]) -> Vec<u8> {
+        kmac256_hash(b"TEST.sig", &[who, msg]).to_vec()
+    }
+
+    fn toy_verify(who: &NodeId, msg: &[u8; 32], sig: &[u8]) -> bool {
+        toy_sig(who, msg) == sig
+    }
+
+    fn setup_at(epoch: u64) -> (PotParams, EpochSnapshot) {
+        let tp = TrustParams { alpha_q: ONE_Q, beta_q: 0, init_q: q_from_basis_points(1000) };
+        let mut reg = Registry::default();
+        let mut ts = TrustState::default();
+        for n in 1..=3 {
+            reg.insert(nid(n), 1000, true);
+            ts.set(nid(n), ONE_Q);
+        }
+        let params = PotParams { trust: tp, lambda_q: ONE_Q, min_bond: 0, slash_noreveal_bps: 1000 };
+        (params, EpochSnapshot::build(epoch, &reg, &ts, &tp, 0))
+    }
+
+    fn propose(gov: &mut Governance, p: ParamProposal, params: &PotParams, snap: &EpochSnapshot) -> Result<ProposalId, &'static str> {
+        let sig = toy_sig(&p.proposer, &p.signing_hash(gov.network_id));
+        gov.propose(p, &sig, &toy_verify, params, snap)
+    }
+
+    fn vote(gov: &mut Governance, id: &ProposalId, n: u8, snap: &EpochSnapshot) -> Result<bool, &'static str> {
+        let v = ParamVote { network_id: gov.network_id, id: *id, who: nid(n), epoch: snap.epoch };
+        gov.vote(&v, &toy_sig(&v.who, &v.signing_hash()), &toy_verify, snap)
+    }
+
+    #[test]
+    fn supermajority_schedules_for_next_epoch() {
+        let (mut params, snap) = setup_at(4);
+        let mut gov = Governance::new(1, 6667, 2).unwrap();
+        let p = ParamProposal { proposer: nid(1), epoch: 4, changes: vec![ParamChange::MinBond(500)] };
+        let id = propose(&mut gov, p.clone(), &params, &snap).unwrap();
+        assert!(propose(&mut gov, p, &params, &snap).is_err());
+
+        assert_eq!(vote(&mut gov, &id, 1, &snap), Ok(false));
+        assert_eq!(vote(&mut gov, &id, 2, &snap), Ok(false)); // 2/3 < 6667 bps
+        assert!(vote(&mut gov, &id, 9, &snap).is_err());
+
+        // stan przeżywa restart
+        let mut gov: Governance = bincode::deserialize(&bincode::serialize(&gov).unwrap()).unwrap();
+        assert_eq!(vote(&mut gov, &id, 3, &snap), Ok(true));
+        assert_eq!(gov.scheduled_epoch(&id), Some(5));
+        let gov2: Governance = bincode::deserialize(&bincode::serialize(&gov).unwrap()).unwrap();
+        assert_eq!(gov2, gov);
+
+        assert!(gov.on_epoch(4, &mut params).is_empty());
+        assert_eq!(gov.on_epoch(5, &mut params), vec![id]);
+        assert_eq!(params.min_bond, 500);
+    }
+
+    #[test]
+    fn unsigned_foreign_or_outsider_messages_rejected() {
+        let (params, snap) = setup_at(4);
+        let mut gov = Governance::new(1, 6667, 2).unwrap();
+        let p = ParamProposal { proposer: nid(1), epoch: 4, changes: vec![ParamChange::MinBond(500)] };
+        assert_eq!(gov.propose(p.clone(), &[0u8; 32], &toy_verify, &params, &snap), Err("invalid proposal signature"));
+        let outsider = ParamProposal { proposer: nid(9), ..p.clone() };
+        assert_eq!(propose(&mut gov, outsider, &params, &snap), Err("proposer has no weight in snapshot"));
+        let id = propose(&mut gov, p, &params, &snap).unwrap();
+
+        let v = ParamVote { network_id: 1, id, who: nid(2), epoch: 4 };
+        assert_eq!(gov.vote(&v, &toy_sig(&nid(3), &v.signing_hash()), &toy_verify, &snap), Err("invalid vote signature"));
+        let foreign = ParamVote { network_id: 2, ..v };
+        let sig = toy_sig(&foreign.who, &foreign.signing_hash());
+        assert_eq!(gov.vote(&foreign, &sig, &toy_verify, &snap), Err("vote from another network"));
+        // podpis z sieci 2 nie pasuje do głosu przepisanego na sieć 1
+        assert_eq!(gov.vote(&v, &sig, &toy_verify, &snap), Err("invalid vote signature"));
+        assert_eq!(gov.tally_q(&id, &snap), Some(0));
+    }
+
+    #[test]
+    fn invalid_or_stale_proposals_rejected() {
+        let (params, snap) = setup_at(4);
+        let mut gov = Governance::new(1, 6667, 2).unwrap();
+        let bad = ParamProposal { proposer: nid(1), epoch: 4, changes: vec![ParamChange::TrustAlpha(ONE_Q + 1)] };
+        assert!(propose(&mut gov, bad, &params, &snap).is_err());
+        let old = ParamProposal { proposer: nid(1), epoch: 1, changes: vec![ParamChange::SlashNorevealBps(200)] };
+        assert!(propose(&mut gov, old.clone(), &params, &snap).is_err());
+        let id = propose(&mut gov, old, &params, &setup_at(1).1).unwrap();
+        assert_eq!(vote(&mut gov, &id, 1, &snap), Err("proposal expired"));
+        gov.prune(4);
+        assert!(!gov.is_pending(&id));
+        assert!(Governance::new(1, 5000, 1).is_err());
+    }
+
+    #[test]
+    fn vote_with_snapshot_older_than_proposal_rejected() {
+        let (params, snap6) = setup_at(6);
+        let snap4 = setup_at(4).1;
+        let mut gov = Governance::new(1, 6667, 2).unwrap();
+        let p = ParamProposal { proposer: nid(1), epoch: 6, changes: vec![ParamChange::MinBond(500)] };
+        let id = propose(&mut gov, p, &params, &snap6).unwrap();
+        for n in 1..=3 {
+            assert_eq!(vote(&mut gov, &id, n, &snap4), Err("snapshot predates proposal"));
+        }
+        assert!(gov.is_pending(&id));
+        assert_eq!(gov.tally_q(&id, &snap4), Some(0));
+    }
+}
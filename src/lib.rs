@@ -6,16 +6,59 @@
 //! - Merkle tree-based weight snapshots
 //! - Sortition-based leader selection
 //! - Equivocation detection and slashing
+//! - Trust-age proofs over epoch snapshot history
+//! - Cumulative-weight fork choice with reorg detection and orphan pool
+//! - Weighted checkpoint finality (2/3 of snapshot weight)
+//! - Equivocation evidence pool and gossip proposal tracker
+//! - Epoch transition pipeline
+//! - Stake/trust-weighted governance of consensus parameters
+//! - Bonding, unbonding period and validator activation queue
+//! - Delegated stake with pro-rata rewards and slashing
+//! - Block issuance schedule and proposer/RANDAO rewards
+//! - Validator RANDAO commit/reveal scheduling
+//! - Slot clock with future-block tolerance and drift detection
+//! - Chain spec and deterministic genesis state
+//! - Deterministic multi-node simulation with Byzantine behaviors
 
+pub mod chain_spec;
 pub mod crypto_kmac_consensus;
+pub mod delegation;
+pub mod epoch;
+pub mod evidence;
+pub mod finality;
+pub mod fork_choice;
+pub mod governance;
 pub mod pot;
+pub mod randao_schedule;
+pub mod rewards;
+pub mod simulation;
+pub mod slot_clock;
 pub mod snapshot;
+pub mod staking;
+pub mod trust_age;
 
 // Re-export main types for convenience
 pub use pot::{
-    EpochSnapshot, LeaderWitness, MerkleProof, NodeId, PotParams, Q, RandaoBeacon,
+    EpochSnapshot, LeaderWitness, MerkleProof, NodeId, PotParams, Proposal, Q, RandaoBeacon,
     Registry, TrustParams, TrustState, ONE_Q, q_from_basis_points, q_from_ratio,
     q_from_ratio128, verify_leader_and_update_trust, verify_leader_with_witness,
     detect_equivocation, slash_equivocation, finalize_epoch_and_slash,
+    checked_qmul, checked_qdiv, checked_q_from_ratio128, prob_threshold_checked,
 };
-pub use snapshot::{SnapshotWitnessExt, WeightWitnessV1};
+pub use chain_spec::{ChainSpec, ConsensusSpec, Genesis, GenesisBalance, GenesisValidator};
+pub use delegation::{AccountId, Delegations};
+pub use epoch::{EpochManager, EpochTransition};
+pub use evidence::{Evidence, EvidencePool, ProposalTracker};
+pub use finality::{Checkpoint, DoubleVote, FinalityGadget, FinalityVote};
+pub use fork_choice::{BlockId, ForkChoice, HeadChange, OrphanPool, ReorgEvent};
+pub use governance::{Governance, ParamChange, ParamProposal, ParamVote, ProposalId};
+pub use randao_schedule::{PendingSecret, RandaoAction, RandaoSchedule};
+pub use rewards::{DelegationSplit, InflationSchedule, RewardLedger, revealers};
+pub use simulation::{Behavior, Partition, SimConfig, SimReport, Simulation, simulate};
+pub use slot_clock::SlotClock;
+pub use snapshot::{
+    DigestMismatch, EntryDiff, SnapshotDigest, SnapshotDigestExt, SnapshotWitnessExt,
+    WeightWitnessV1,
+};
+pub use staking::{Staking, StakingChanges, StakingOp, UnbondingEntry};
+pub use trust_age::{SnapshotHistory, TrustAgeEntry, TrustAgeProof, verify_trust_age};
@@ -36,7 +36,8 @@ use sharks::{Sharks, Share};
  * Constants
  * ====================================================================================== */
 
-const WALLET_VERSION: u32 = 4;
+const WALLET_VERSION: u32 = 5;
+const WALLET_VERSION_MIN: u32 = 4; // oldest format that can be migrated (see MIGRATIONS)
 const BECH32_HRP: &str = "tt";
 const WALLET_MAX_SIZE: u64 = 1 << 20; // 1 MiB
 const MIN_PASSWORD_LEN: usize = 12;
@@ -45,6 +46,16 @@ const ARGON2_TIME_COST: u32 = 3;
 const ARGON2_LANES: u32 = 1;
 const SHAMIR_MAX_N: u8 = 255;
 const SHAMIR_MIN_M: u8 = 2;
+const PAY_URI_SCHEME: &str = "truetrust:";
+const CONTACT_LABEL_MAX: usize = 64;
+const VIEW_KEY_VERSION: u32 = 1;
+const ADDR_VERSION: u8 = 0x01;
+const ADDR_VERSION_DIV: u8 = 0x02; // payload carries a u32 diversifier
+const MAX_DIV_WINDOW: u32 = 4096;
+const MAX_INDEX_WINDOW: u32 = 1024;
+const MAX_SCAN_KEYS: u64 = 8192; // HD indices x (diversifiers + 1) per keysearch
+const BACKUP_MAGIC: [u8; 8] = *b"TTBACKUP";
+const BACKUP_VERSION: u32 = 1;
 
 /* =========================================================================================
  * CLI
@@ -71,7 +82,7 @@ enum PepperFlag {
 
 #[derive(Subcommand, Debug)]
 enum Cmd {
-    /// Initialize a new encrypted wallet file (v4)
+    /// Initialize a new encrypted wallet file (v5)
     WalletInit {
         #[arg(long)] file: PathBuf,
         #[arg(long, default_value_t = true)] argon2: bool,
@@ -81,11 +92,49 @@ enum Cmd {
     },
 
     /// Show public address (bech32) and base public keys
-    WalletAddr { #[arg(long)] file: PathBuf },
+    WalletAddr {
+        #[arg(long)] file: PathBuf,
+        /// HD account (0 = default)
+        #[arg(long, default_value_t = 0)] account: u32,
+        /// HD address index within the account (0 = default)
+        #[arg(long, default_value_t = 0)] index: u32,
+        /// Diversified (per-invoice) address number; unlinkable to the others
+        #[arg(long)] diversifier: Option<u32>,
+    },
 
     /// Export keys (public or secret) — secret export requires --out file
     WalletExport { #[arg(long)] file: PathBuf, #[arg(long)] secret: bool, #[arg(long)] out: Option<PathBuf> },
 
+    /// Export a view-only key (scan secret + public keys, no spend key) for watch-only scanning
+    WalletExportView {
+        #[arg(long)] file: PathBuf,
+        #[arg(long)] out: PathBuf,
+        #[arg(long, default_value_t = 0)] account: u32,
+        #[arg(long, default_value_t = 0)] index: u32,
+    },
+
+    /// Upgrade an older wallet file to the current format (backs up the original first)
+    WalletMigrate { #[arg(long)] file: PathBuf, #[arg(long)] dry_run: bool },
+
+    /// Write a portable encrypted backup (own password, Argon2id, no pepper)
+    WalletBackup {
+        #[arg(long)] file: PathBuf,
+        /// Backup file, or a directory to add a new timestamped backup to
+        #[arg(long)] target: PathBuf,
+    },
+
+    /// Restore a wallet from a backup (re-encrypted under a new password)
+    WalletRestore {
+        #[arg(long)] backup: PathBuf,
+        #[arg(long)] file: PathBuf,
+        /// Overwrite an existing wallet file
+        #[arg(long)] force: bool,
+        #[arg(long, default_value_t = true)] argon2: bool,
+        #[arg(long, value_enum, default_value_t = AeadFlag::GcmSiv)] aead: AeadFlag,
+        #[arg(long, value_enum, default_value_t = PepperFlag::OsLocal)] pepper: PepperFlag,
+        #[arg(long, default_value_t = 1024)] pad_block: u16,
+    },
+
     /// Change wallet password (re-encrypt in place)
     WalletRekey {
         #[arg(long)] file: PathBuf,
@@ -102,15 +151,42 @@ enum Cmd {
     ScanHeader { #[arg(long)] filters: PathBuf, #[arg(long)] file: PathBuf },
 
     // ====== Keysearch modes ======
-    KeysearchPairs { #[arg(long)] wallet: PathBuf, #[arg(long)] file: PathBuf },
-    KeysearchStateless { #[arg(long)] wallet: PathBuf, #[arg(long)] file: PathBuf },
-    KeysearchHeader { #[arg(long)] wallet: PathBuf, #[arg(long)] file: PathBuf },
+    // --view <file>: watch-only mode using a key from wallet-export-view (no password, no spend key)
+    // --div-window <n>: also try diversified scan keys 0..n
+    // --account <a> --index-window <n>: wallet mode scans HD addresses (a, 0..n)
+    KeysearchPairs {
+        #[arg(long, required_unless_present = "view", conflicts_with = "view")] wallet: Option<PathBuf>,
+        #[arg(long)] view: Option<PathBuf>,
+        #[arg(long)] file: PathBuf,
+        #[arg(long, default_value_t = 0)] div_window: u32,
+        #[arg(long, default_value_t = 0, conflicts_with = "view")] account: u32,
+        #[arg(long, default_value_t = 1, conflicts_with = "view")] index_window: u32,
+        /// Only accept hints bound to this network id (default: $TT_NET_ID, else unbound)
+        #[arg(long)] net_id: Option<u32>,
+    },
+    KeysearchStateless {
+        #[arg(long, required_unless_present = "view", conflicts_with = "view")] wallet: Option<PathBuf>,
+        #[arg(long)] view: Option<PathBuf>,
+        #[arg(long)] file: PathBuf,
+        #[arg(long, default_value_t = 0)] div_window: u32,
+        #[arg(long, default_value_t = 0, conflicts_with = "view")] account: u32,
+        #[arg(long, default_value_t = 1, conflicts_with = "view")] index_window: u32,
+    },
+    KeysearchHeader {
+        #[arg(long, required_unless_present = "view", conflicts_with = "view")] wallet: Option<PathBuf>,
+        #[arg(long)] view: Option<PathBuf>,
+        #[arg(long)] file: PathBuf,
+        #[arg(long, default_value_t = 0)] div_window: u32,
+        #[arg(long, default_value_t = 0, conflicts_with = "view")] account: u32,
+        #[arg(long, default_value_t = 1, conflicts_with = "view")] index_window: u32,
+    },
 
     // ====== Sender tools ======
     BuildEncHint {
         #[arg(long)] scan_pk: String,
         #[arg(long)] c_out: String,
         #[arg(long)] r_blind_hex: Option<String>,
+        /// Bind the hint to a network id (default: $TT_NET_ID, else unbound)
         #[arg(long)] net_id: Option<u32>,
         #[arg(long)] value: Option<u64>,
         #[arg(long)] mask_value: bool,
@@ -132,11 +208,48 @@ enum Cmd {
         /// comma-separated or repeated --in
         #[arg(long, value_delimiter=',')] input: Vec<PathBuf>,
         #[arg(long)] out: PathBuf,
+        /// read further shard paths from stdin until enough are collected
+        #[arg(long)] interactive: bool,
         #[arg(long, default_value_t = true)] argon2: bool,
         #[arg(long, value_enum, default_value_t = AeadFlag::GcmSiv)] aead: AeadFlag,
         #[arg(long, value_enum, default_value_t = PepperFlag::OsLocal)] pepper: PepperFlag,
         #[arg(long, default_value_t = 1024)] pad_block: u16,
     },
+
+    /// Check shard MACs and metadata without reconstructing the secret
+    ShardsVerify {
+        #[arg(long, value_delimiter=',', required = true)] input: Vec<PathBuf>,
+    },
+
+    // ====== Address book (stored encrypted in the wallet file) ======
+    ContactsAdd { #[arg(long)] file: PathBuf, #[arg(long)] label: String, #[arg(long)] address: String },
+    ContactsList { #[arg(long)] file: PathBuf },
+    ContactsRemove { #[arg(long)] file: PathBuf, #[arg(long)] label: String },
+
+    // ====== Payment request URIs ======
+    /// Build a truetrust: payment URI
+    PayUriBuild {
+        #[arg(long)] address: String,
+        #[arg(long)] amount: Option<u64>,
+        #[arg(long)] memo: Option<String>,
+    },
+    /// Parse and validate a truetrust: payment URI
+    PayUriParse { #[arg(long)] uri: String },
+
+    // ====== Consensus simulation ======
+    /// Run a deterministic in-process consensus simulation (virtual time)
+    Simulate {
+        #[arg(long, default_value_t = 4)] honest: usize,
+        #[arg(long, default_value_t = 0)] equivocators: usize,
+        #[arg(long, default_value_t = 0)] withholders: usize,
+        #[arg(long, default_value_t = 0)] invalid: usize,
+        #[arg(long, default_value_t = 64)] slots: u64,
+        #[arg(long, default_value_t = 8)] slots_per_epoch: u64,
+        /// 32-byte hex seed (default: all zero)
+        #[arg(long)] seed: Option<String>,
+        /// FROM:UNTIL slots during which the node set is split in two halves
+        #[arg(long)] partition: Option<String>,
+    },
 }
 
 /* =========================================================================================
@@ -175,16 +288,37 @@ enum KdfKind {
     Argon2idV1 { mem_kib: u32, time_cost: u32, lanes: u32, salt32: [u8; 32] },
 }
 
+/// v4 payload (master seed only)
 #[derive(Clone, Serialize, Deserialize, Zeroize)]
 #[zeroize(drop)]
 struct WalletSecretPayloadV2 {
     master32: [u8; 32],
 }
 
+/// v5 payload: master seed + encrypted address book
+#[derive(Clone, Serialize, Deserialize, Zeroize)]
+#[zeroize(drop)]
+struct WalletSecretPayloadV3 {
+    master32: [u8; 32],
+    contacts: Vec<Contact>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Zeroize)]
+struct Contact {
+    label: String,
+    address: String,
+}
+
+impl WalletSecretPayloadV3 {
+    fn new(master32: [u8; 32]) -> Self {
+        Self { master32, contacts: Vec::new() }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 struct WalletFile {
     header: WalletHeader,
-    /// AEAD over padded(serialized WalletSecretPayloadV2); AAD = bincode(header)
+    /// AEAD over padded(serialized WalletSecretPayloadV3); AAD = bincode(header)
     enc: Vec<u8>,
 }
 
@@ -202,28 +336,286 @@ pub struct Keyset {
 
 impl Keyset {
     fn from_master(master32: &[u8; 32]) -> Self {
-        let spend32 = ck::kmac256_derive_key(master32, b"TT-SPEND.v1", b"seed");
-        let scan32  = ck::kmac256_derive_key(master32, b"TT-SCAN.v1",  b"seed");
+        let spend32 = Zeroizing::new(ck::kmac256_derive_key(master32, b"TT-SPEND.v1", b"seed"));
+        let scan32  = Zeroizing::new(ck::kmac256_derive_key(master32, b"TT-SCAN.v1",  b"seed"));
+        let spend_sk = Ed25519Secret::from_bytes(&spend32);
+        let spend_pk = Ed25519Public::from(&spend_sk);
+        let scan_sk  = X25519Secret::from(*scan32);
+        let scan_pk  = X25519Public::from(&scan_sk);
+        Self { spend_sk, spend_pk, scan_sk, scan_pk }
+    }
+
+    /// HD derivation: (account, index) -> child seed -> keyset.
+    /// (0, 0) is the base keyset, so existing addresses stay unchanged.
+    fn derive(master32: &[u8; 32], account: u32, index: u32) -> Self {
+        if account == 0 && index == 0 {
+            return Self::from_master(master32);
+        }
+        let mut path = [0u8; 8];
+        path[..4].copy_from_slice(&account.to_le_bytes());
+        path[4..].copy_from_slice(&index.to_le_bytes());
+        let child = Zeroizing::new(ck::kmac256_derive_key(master32, b"TT-HD.v1", &path));
+        Self::from_master(&child)
+    }
+
+    /// Diversified keyset `d` (per-invoice address). The scan key is a tweak of
+    /// this scan secret, so a view key can still find payments; the spend key
+    /// comes from the spend secret, so diversified addresses share no public key.
+    fn diversified(&self, d: u32) -> Self {
+        let scan32 = diversified_scan_secret(&Zeroizing::new(self.scan_sk.to_bytes()), d);
+        let spend_base = Zeroizing::new(self.spend_sk.to_bytes());
+        let spend32 = Zeroizing::new(ck::kmac256_derive_key(&*spend_base, b"TT-DIV-SPEND.v1", &d.to_le_bytes()));
         let spend_sk = Ed25519Secret::from_bytes(&spend32);
         let spend_pk = Ed25519Public::from(&spend_sk);
-        let scan_sk  = X25519Secret::from(scan32);
+        let scan_sk  = X25519Secret::from(*scan32);
         let scan_pk  = X25519Public::from(&scan_sk);
         Self { spend_sk, spend_pk, scan_sk, scan_pk }
     }
 }
 
+fn diversified_scan_secret(scan_sk: &[u8; 32], d: u32) -> Zeroizing<[u8; 32]> {
+    Zeroizing::new(ck::kmac256_derive_key(scan_sk, b"TT-DIV-SCAN.v1", &d.to_le_bytes()))
+}
+
+/// (diversifier, scan secret); `None` is the undiversified key
+type ScanSecrets = Vec<(Option<u32>, Zeroizing<[u8; 32]>)>;
+
+/// Scan secrets to try: the base key, then diversifiers `0..window`
+fn scan_secrets(base: &[u8; 32], window: u32) -> Result<ScanSecrets> {
+    ensure!(window <= MAX_DIV_WINDOW, "div window too large (max {})", MAX_DIV_WINDOW);
+    let mut out = vec![(None, Zeroizing::new(*base))];
+    out.extend((0..window).map(|d| (Some(d), diversified_scan_secret(base, d))));
+    Ok(out)
+}
+
+fn div_label(d: Option<u32>) -> String {
+    d.map(|d| format!(" div={}", d)).unwrap_or_default()
+}
+
 /* =========================================================================================
  * Address (bech32)
  * ====================================================================================== */
 
-fn bech32_addr(scan_pk: &X25519Public, spend_pk: &Ed25519Public) -> Result<String> {
+fn encode_addr(payload: &[u8]) -> Result<String> {
     use bech32::{ToBase32, Variant::Bech32m, encode};
     debug_assert!(BECH32_HRP.chars().all(|c| !c.is_ascii_uppercase()), "bech32 HRP must be lowercase");
+    Ok(encode(BECH32_HRP, payload.to_base32(), Bech32m)?)
+}
+
+fn bech32_addr(scan_pk: &X25519Public, spend_pk: &Ed25519Public) -> Result<String> {
     let mut payload = Vec::with_capacity(65);
-    payload.push(0x01); // version
+    payload.push(ADDR_VERSION);
     payload.extend_from_slice(scan_pk.as_bytes());
     payload.extend_from_slice(spend_pk.as_bytes());
-    Ok(encode(BECH32_HRP, payload.to_base32(), Bech32m)?)
+    encode_addr(&payload)
+}
+
+/// Diversified address: version 0x02 || d (u32 LE) || scan_pk || spend_pk
+fn bech32_div_addr(d: u32, scan_pk: &X25519Public, spend_pk: &Ed25519Public) -> Result<String> {
+    let mut payload = Vec::with_capacity(69);
+    payload.push(ADDR_VERSION_DIV);
+    payload.extend_from_slice(&d.to_le_bytes());
+    payload.extend_from_slice(scan_pk.as_bytes());
+    payload.extend_from_slice(spend_pk.as_bytes());
+    encode_addr(&payload)
+}
+
+/// Validate a bech32m wallet address (HRP, variant, version byte and length)
+fn parse_bech32_addr(addr: &str) -> Result<(X25519Public, Ed25519Public)> {
+    let (scan, spend, _) = parse_bech32_addr_ext(addr)?;
+    Ok((scan, spend))
+}
+
+/// Like `parse_bech32_addr`, also returning the diversifier of a v2 address
+fn parse_bech32_addr_ext(addr: &str) -> Result<(X25519Public, Ed25519Public, Option<u32>)> {
+    use bech32::{FromBase32, Variant};
+    let (hrp, data, variant) = bech32::decode(addr.trim()).map_err(|e| anyhow!("bad address: {e}"))?;
+    ensure!(hrp == BECH32_HRP, "bad address HRP: expected '{}', got '{}'", BECH32_HRP, hrp);
+    ensure!(variant == Variant::Bech32m, "bad address: expected bech32m encoding");
+    let payload = Vec::<u8>::from_base32(&data).map_err(|e| anyhow!("bad address payload: {e}"))?;
+    let (div, keys) = match (payload.first(), payload.len()) {
+        (Some(&ADDR_VERSION), 65) => (None, &payload[1..]),
+        (Some(&ADDR_VERSION_DIV), 69) => (Some(u32::from_le_bytes(payload[1..5].try_into()?)), &payload[5..]),
+        _ => bail!("bad address payload (len {}, want 65 with version 0x01 or 69 with version 0x02)", payload.len()),
+    };
+    let mut scan = [0u8; 32]; scan.copy_from_slice(&keys[..32]);
+    let mut spend = [0u8; 32]; spend.copy_from_slice(&keys[32..64]);
+    let spend_pk = Ed25519Public::from_bytes(&spend).map_err(|_| anyhow!("bad address: invalid spend key"))?;
+    Ok((X25519Public::from(scan), spend_pk, div))
+}
+
+/* =========================================================================================
+ * View keys (watch-only)
+ * ====================================================================================== */
+
+/// View-only credential: can detect incoming outputs, cannot spend or sign
+#[derive(Serialize, Deserialize, Zeroize)]
+#[zeroize(drop)]
+struct ViewKeyFile {
+    version: u32,
+    scan_sk: String,
+    scan_pk: String,
+    spend_pk: String,
+    address: String,
+}
+
+impl ViewKeyFile {
+    fn from_keyset(ks: &Keyset) -> Result<Self> {
+        Ok(Self {
+            version: VIEW_KEY_VERSION,
+            scan_sk: hex::encode(ks.scan_sk.to_bytes()),
+            scan_pk: hex::encode(ks.scan_pk.as_bytes()),
+            spend_pk: hex::encode(ks.spend_pk.to_bytes()),
+            address: bech32_addr(&ks.scan_pk, &ks.spend_pk)?,
+        })
+    }
+
+    /// Returns the scan secret after checking it matches the recorded public keys/address
+    fn scan_secret(&self) -> Result<Zeroizing<[u8; 32]>> {
+        ensure!(self.version == VIEW_KEY_VERSION,
+            "view key version unsupported (have {}, want {})", self.version, VIEW_KEY_VERSION);
+        let sk = Zeroizing::new(hex32(&self.scan_sk).context("view key: scan_sk")?);
+        let scan_pk = X25519Public::from(&X25519Secret::from(*sk));
+        ensure!(hex::encode(scan_pk.as_bytes()) == self.scan_pk.trim(), "view key: scan_sk does not match scan_pk");
+        let (addr_scan, addr_spend) = parse_bech32_addr(&self.address)?;
+        ensure!(addr_scan == scan_pk && hex::encode(addr_spend.to_bytes()) == self.spend_pk.trim(),
+            "view key: address does not match keys");
+        Ok(sk)
+    }
+}
+
+fn load_view_key(path: &Path) -> Result<Zeroizing<[u8; 32]>> {
+    let meta = fs::metadata(path).with_context(|| format!("stat {}", path.display()))?;
+    ensure!(meta.len() <= 4096, "view key file too large");
+    let bytes = Zeroizing::new(fs::read(path).with_context(|| format!("read {}", path.display()))?);
+    let vk: ViewKeyFile = serde_json::from_slice(&bytes).with_context(|| format!("parse {}", path.display()))?;
+    vk.scan_secret()
+}
+
+/// (hit label, base scan secret); diversifiers are added on top by `keysearch_ctxs`
+type BaseScanSecrets = Vec<(String, Zeroizing<[u8; 32]>)>;
+
+/// Scan secrets of HD addresses (account, 0..index_window). The label names the
+/// index only when more than the default address is scanned.
+fn wallet_scan_secrets(master32: &[u8; 32], account: u32, index_window: u32) -> Result<BaseScanSecrets> {
+    ensure!((1..=MAX_INDEX_WINDOW).contains(&index_window),
+        "index window must be 1..={}", MAX_INDEX_WINDOW);
+    let plain = account == 0 && index_window == 1;
+    Ok((0..index_window)
+        .map(|i| {
+            let label = if plain { String::new() } else { format!(" account={} index={}", account, i) };
+            (label, Zeroizing::new(Keyset::derive(master32, account, i).scan_sk.to_bytes()))
+        })
+        .collect())
+}
+
+/// Scan secrets for keysearch: either from the full wallet (password) or a
+/// watch-only view key, which covers exactly the address it was exported for
+fn load_scan_secrets(wallet: Option<PathBuf>, view: Option<PathBuf>, account: u32, index_window: u32) -> Result<BaseScanSecrets> {
+    match (wallet, view) {
+        (_, Some(v)) => Ok(vec![(String::new(), load_view_key(&v)?)]),
+        (Some(w), None) => wallet_scan_secrets(&load_secret(&w)?.master32, account, index_window),
+        (None, None) => bail!("either --wallet or --view is required"),
+    }
+}
+
+/// One keysearch context per base secret and diversifier, with its hit label
+fn keysearch_ctxs(bases: BaseScanSecrets, div_window: u32) -> Result<Vec<(String, pot80_zk_host::keysearch::KeySearchCtx)>> {
+    ensure!(bases.len() as u64 * (u64::from(div_window) + 1) <= MAX_SCAN_KEYS,
+        "too many scan keys (index window x div window > {})", MAX_SCAN_KEYS);
+    let mut out = Vec::new();
+    for (label, base) in bases {
+        for (d, sk) in scan_secrets(&base, div_window)? {
+            out.push((format!("{}{}", label, div_label(d)), pot80_zk_host::keysearch::KeySearchCtx::new(*sk)));
+        }
+    }
+    Ok(out)
+}
+
+/* =========================================================================================
+ * Payment request URIs (truetrust:<addr>?amount=<u64>&memo=<pct-encoded>)
+ * ====================================================================================== */
+
+#[derive(Debug, PartialEq, Eq)]
+struct PaymentRequest {
+    address: String,
+    amount: Option<u64>,
+    memo: Option<String>,
+}
+
+fn pct_encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{:02X}", b));
+        }
+    }
+    out
+}
+
+fn pct_decode(s: &str) -> Result<String> {
+    let b = s.as_bytes();
+    let mut out = Vec::with_capacity(b.len());
+    let mut i = 0;
+    while i < b.len() {
+        if b[i] == b'%' {
+            // dokładnie dwie cyfry hex (from_str_radix sam przepuściłby np. "%+1")
+            let h = b.get(i+1..i+3)
+                .filter(|h| h.iter().all(u8::is_ascii_hexdigit))
+                .ok_or_else(|| anyhow!("bad percent-escape at byte {}", i))?;
+            out.push(u8::from_str_radix(std::str::from_utf8(h)?, 16)?);
+            i += 3;
+        } else {
+            out.push(b[i]);
+            i += 1;
+        }
+    }
+    Ok(String::from_utf8(out)?)
+}
+
+impl PaymentRequest {
+    fn to_uri(&self) -> String {
+        let mut uri = format!("{}{}", PAY_URI_SCHEME, self.address);
+        let mut params = Vec::new();
+        if let Some(a) = self.amount { params.push(format!("amount={}", a)); }
+        if let Some(m) = &self.memo { params.push(format!("memo={}", pct_encode(m))); }
+        if !params.is_empty() {
+            uri.push('?');
+            uri.push_str(&params.join("&"));
+        }
+        uri
+    }
+
+    fn parse(uri: &str) -> Result<Self> {
+        let rest = uri.trim().strip_prefix(PAY_URI_SCHEME)
+            .ok_or_else(|| anyhow!("not a {} URI", PAY_URI_SCHEME))?;
+        let (address, query) = match rest.split_once('?') {
+            Some((a, q)) => (a, Some(q)),
+            None => (rest, None),
+        };
+        parse_bech32_addr(address)?;
+
+        let mut req = PaymentRequest { address: address.to_string(), amount: None, memo: None };
+        for kv in query.unwrap_or("").split('&').filter(|p| !p.is_empty()) {
+            let (k, v) = kv.split_once('=').ok_or_else(|| anyhow!("bad URI parameter: {kv}"))?;
+            match k {
+                "amount" => {
+                    ensure!(req.amount.is_none(), "duplicate amount parameter");
+                    req.amount = Some(v.parse().map_err(|_| anyhow!("bad amount: {v}"))?);
+                }
+                "memo" => {
+                    ensure!(req.memo.is_none(), "duplicate memo parameter");
+                    req.memo = Some(pct_decode(v)?);
+                }
+                // unknown req-* parameters must be understood; others are ignored
+                _ if k.starts_with("req-") => bail!("unsupported required parameter: {k}"),
+                _ => {}
+            }
+        }
+        Ok(req)
+    }
 }
 
 /* =========================================================================================
@@ -269,8 +661,8 @@ impl PepperProvider for OsLocalPepper {
             use std::os::unix::fs::OpenOptionsExt;
             match OpenOptions::new().create_new(true).write(true).mode(0o600).open(&path) {
                 Ok(mut f) => {
-                    let mut p = [0u8;32]; OsRng.fill_bytes(&mut p);
-                    f.write_all(&p)?;
+                    let mut p = Zeroizing::new([0u8;32]); OsRng.fill_bytes(&mut *p);
+                    f.write_all(&*p)?;
                     f.sync_all()?;
                     return Ok(Zeroizing::new(p.to_vec()));
                 }
@@ -284,8 +676,8 @@ impl PepperProvider for OsLocalPepper {
         {
             match OpenOptions::new().create_new(true).write(true).open(&path) {
                 Ok(mut f) => {
-                    let mut p = [0u8;32]; OsRng.fill_bytes(&mut p);
-                    f.write_all(&p)?;
+                    let mut p = Zeroizing::new([0u8;32]); OsRng.fill_bytes(&mut *p);
+                    f.write_all(&*p)?;
                     f.sync_all()?;
                     return Ok(Zeroizing::new(p.to_vec()));
                 }
@@ -317,16 +709,17 @@ fn pepper_provider(pol: &PepperPolicy) -> Box<dyn PepperProvider> {
 fn derive_kdf_key(password: &str, hdr: &KdfHeader, pepper: &[u8]) -> [u8; 32] {
     match &hdr.kind {
         KdfKind::Kmac256V1 { salt32 } => {
-            let k1 = ck::kmac256_derive_key(password.as_bytes(), b"TT-KDF.v4.kmac.pre", salt32);
-            ck::kmac256_derive_key(&k1, b"TT-KDF.v4.kmac.post", pepper)
+            let k1 = Zeroizing::new(ck::kmac256_derive_key(password.as_bytes(), b"TT-KDF.v4.kmac.pre", salt32));
+            ck::kmac256_derive_key(&*k1, b"TT-KDF.v4.kmac.post", pepper)
         }
         KdfKind::Argon2idV1 { mem_kib, time_cost, lanes, salt32 } => {
             let params = Params::new(*mem_kib, *time_cost, *lanes, Some(32)).expect("argon2 params");
             let a2 = Argon2::new_with_secret(pepper, Algorithm::Argon2id, Version::V0x13, params)
                 .expect("argon2 new_with_secret");
-            let mut out = [0u8; 32];
-            a2.hash_password_into(password.as_bytes(), salt32, &mut out).expect("argon2");
-            ck::kmac256_derive_key(&out, b"TT-KDF.v4.post", salt32)
+            // surowy wynik Argon2 też jest sekretem – czyścimy po użyciu
+            let mut out = Zeroizing::new([0u8; 32]);
+            a2.hash_password_into(password.as_bytes(), salt32, &mut *out).expect("argon2");
+            ck::kmac256_derive_key(&*out, b"TT-KDF.v4.post", salt32)
         }
     }
 }
@@ -354,7 +747,7 @@ fn unpad(mut v: Vec<u8>) -> Result<Vec<u8>> {
     Ok(v)
 }
 
-fn encrypt_wallet(payload: &WalletSecretPayloadV2, password: &str, hdr: &WalletHeader) -> Result<Vec<u8>> {
+fn encrypt_wallet(payload: &WalletSecretPayloadV3, password: &str, hdr: &WalletHeader) -> Result<Vec<u8>> {
     let prov = pepper_provider(&hdr.pepper);
     let pepper = prov.get(&hdr.wallet_id)?;
     let key = Zeroizing::new(derive_kdf_key(password, &hdr.kdf, &pepper));
@@ -379,7 +772,7 @@ fn encrypt_wallet(payload: &WalletSecretPayloadV2, password: &str, hdr: &WalletH
     }
 }
 
-fn decrypt_wallet(enc: &[u8], password: &str, hdr: &WalletHeader) -> Result<WalletSecretPayloadV2> {
+fn decrypt_wallet(enc: &[u8], password: &str, hdr: &WalletHeader) -> Result<WalletSecretPayloadV3> {
     let prov = pepper_provider(&hdr.pepper);
     let pepper = prov.get(&hdr.wallet_id)?;
     let key = Zeroizing::new(derive_kdf_key(password, &hdr.kdf, &pepper));
@@ -401,34 +794,177 @@ fn decrypt_wallet(enc: &[u8], password: &str, hdr: &WalletHeader) -> Result<Wall
         }
     };
 
-    let unpadded = unpad(pt.to_vec())?;
-    let w: WalletSecretPayloadV2 = bincode::options()
-        .with_limit(WALLET_MAX_SIZE as u64)
-        .deserialize(&unpadded)?;
+    let unpadded = Zeroizing::new(unpad(pt.to_vec())?);
+    let (current, _) = migrate_payload(hdr.version, unpadded)?;
+    let w: WalletSecretPayloadV3 = bincode::options().with_limit(WALLET_MAX_SIZE).deserialize(&current)?;
     Ok(w)
 }
 
+/* =========================================================================================
+ * Wallet migrations (decrypted payload, version N -> N+1)
+ * ====================================================================================== */
+
+struct Migration {
+    from: u32,
+    describe: &'static str,
+    apply: fn(&[u8]) -> Result<Zeroizing<Vec<u8>>>,
+}
+
+/// Ordered chain; a wallet at version N runs every step with `from >= N`
+const MIGRATIONS: &[Migration] = &[
+    Migration { from: 4, describe: "v4 -> v5: add encrypted address book", apply: migrate_v4_to_v5 },
+];
+
+fn migrate_v4_to_v5(pt: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+    let opts = bincode::options().with_limit(WALLET_MAX_SIZE);
+    let v4: WalletSecretPayloadV2 = opts.deserialize(pt).context("decode v4 payload")?;
+    let v5 = WalletSecretPayloadV3::new(v4.master32);
+    Ok(Zeroizing::new(opts.serialize(&v5)?))
+}
+
+/// Bring a decrypted payload up to WALLET_VERSION; returns the applied steps
+fn migrate_payload(version: u32, pt: Zeroizing<Vec<u8>>) -> Result<(Zeroizing<Vec<u8>>, Vec<&'static str>)> {
+    ensure!((WALLET_VERSION_MIN..=WALLET_VERSION).contains(&version),
+        "wallet version unsupported (have {}, want {}..={})", version, WALLET_VERSION_MIN, WALLET_VERSION);
+    let mut cur = pt;
+    let mut steps = Vec::new();
+    for v in version..WALLET_VERSION {
+        let m = MIGRATIONS.iter().find(|m| m.from == v)
+            .ok_or_else(|| anyhow!("no migration from wallet v{}", v))?;
+        cur = (m.apply)(&cur).with_context(|| m.describe)?;
+        steps.push(m.describe);
+    }
+    Ok((cur, steps))
+}
+
+/// `<file>.v<N>.bak` next to the wallet
+fn migration_backup_path(path: &Path, version: u32) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".v{}.bak", version));
+    PathBuf::from(name)
+}
+
+/* =========================================================================================
+ * Encrypted backups
+ * The decrypted payload is sealed under a separate backup password with no
+ * pepper, so the backup restores on any machine (an OS-local pepper would not).
+ * ====================================================================================== */
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct BackupHeader {
+    magic: [u8; 8],
+    version: u32,
+    /// Payload format inside; older ones go through MIGRATIONS on restore
+    wallet_version: u32,
+    wallet_id: [u8; 16],
+    created_unix: u64,
+    kdf: KdfHeader,
+    nonce24: [u8; 24],
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct BackupFile {
+    header: BackupHeader,
+    /// XChaCha20-Poly1305 over the serialized payload; AAD = bincode(header)
+    enc: Vec<u8>,
+    /// KMAC over header || enc, checked before asking for the password
+    checksum: [u8; 32],
+}
+
+fn backup_checksum(header: &BackupHeader, enc: &[u8]) -> Result<[u8; 32]> {
+    let mut data = bincode::options().with_limit(WALLET_MAX_SIZE).serialize(header)?;
+    data.extend_from_slice(enc);
+    Ok(ck::kmac256_tag(&header.wallet_id, b"TT-BACKUP.sum.v1", &data))
+}
+
+fn backup_cipher(password: &str, header: &BackupHeader) -> Result<(XChaCha20Poly1305, Vec<u8>)> {
+    let key = Zeroizing::new(derive_kdf_key(password, &header.kdf, &[]));
+    let cipher = XChaCha20Poly1305::new_from_slice(&*key).map_err(|_| anyhow!("bad XChaCha key"))?;
+    let aad = bincode::options().with_limit(WALLET_MAX_SIZE).serialize(header)?;
+    Ok((cipher, aad))
+}
+
+/// Seal a serialized payload of format `wallet_version`
+fn seal_backup(pt: &[u8], wallet_version: u32, wallet_id: [u8; 16], password: &str, kdf: KdfHeader) -> Result<BackupFile> {
+    let mut nonce24 = [0u8; 24]; OsRng.fill_bytes(&mut nonce24);
+    let created_unix = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let header = BackupHeader { magic: BACKUP_MAGIC, version: BACKUP_VERSION, wallet_version, wallet_id, created_unix, kdf, nonce24 };
+    let (cipher, aad) = backup_cipher(password, &header)?;
+    let enc = cipher.encrypt(Nonce24::from_slice(&nonce24), chacha20poly1305::aead::Payload { msg: pt, aad: &aad })
+        .map_err(|e| anyhow!("encrypt: {e}"))?;
+    let checksum = backup_checksum(&header, &enc)?;
+    Ok(BackupFile { header, enc, checksum })
+}
+
+/// Integrity and version checks that need no password
+fn check_backup(b: &BackupFile) -> Result<()> {
+    ensure!(b.header.magic == BACKUP_MAGIC, "not a wallet backup");
+    ensure!(b.header.version == BACKUP_VERSION,
+        "backup version unsupported (have {}, want {})", b.header.version, BACKUP_VERSION);
+    ensure!((WALLET_VERSION_MIN..=WALLET_VERSION).contains(&b.header.wallet_version),
+        "backup wallet version unsupported (have {}, want {}..={})", b.header.wallet_version, WALLET_VERSION_MIN, WALLET_VERSION);
+    ensure!(backup_checksum(&b.header, &b.enc)? == b.checksum, "backup checksum mismatch (file corrupted)");
+    Ok(())
+}
+
+/// Decrypt and migrate to the current payload format
+fn open_backup(b: &BackupFile, password: &str) -> Result<WalletSecretPayloadV3> {
+    check_backup(b)?;
+    let (cipher, aad) = backup_cipher(password, &b.header)?;
+    let pt = Zeroizing::new(cipher.decrypt(Nonce24::from_slice(&b.header.nonce24), chacha20poly1305::aead::Payload { msg: &b.enc, aad: &aad })
+        .map_err(|_| anyhow!("backup decrypt failed (wrong backup password?)"))?);
+    let (current, _) = migrate_payload(b.header.wallet_version, pt)?;
+    Ok(bincode::options().with_limit(WALLET_MAX_SIZE).deserialize(&current)?)
+}
+
+fn load_backup(path: &Path) -> Result<BackupFile> {
+    let meta = fs::metadata(path).with_context(|| format!("stat {}", path.display()))?;
+    ensure!(meta.len() <= WALLET_MAX_SIZE, "backup file too large");
+    let buf = fs::read(path).with_context(|| format!("read {}", path.display()))?;
+    let b: BackupFile = bincode::options().with_limit(WALLET_MAX_SIZE).deserialize(&buf)
+        .with_context(|| format!("parse {}", path.display()))?;
+    check_backup(&b)?;
+    Ok(b)
+}
+
+/// A directory target gets `ttbackup-id<wallet_id>-<unix>.bin`, so older backups are kept
+fn backup_target_path(target: &Path, h: &BackupHeader) -> Result<PathBuf> {
+    ensure!(!target.to_string_lossy().contains("://"),
+        "remote targets are not supported; point --target at a synced directory");
+    if target.is_dir() {
+        return Ok(target.join(format!("ttbackup-id{}-{}.bin", hex::encode(h.wallet_id), h.created_unix)));
+    }
+    Ok(target.to_path_buf())
+}
+
 /* =========================================================================================
  * Helpers (Bloom/scan)
  * ====================================================================================== */
 
-fn load_wallet_file(path: &PathBuf) -> Result<WalletFile> {
+fn load_wallet_file(path: &Path) -> Result<WalletFile> {
     let meta = fs::metadata(path).with_context(|| format!("stat {}", path.display()))?;
     ensure!(meta.len() <= WALLET_MAX_SIZE, "wallet file too large");
     let buf = fs::read(path).with_context(|| format!("read {}", path.display()))?;
     let wf: WalletFile = bincode::options().with_limit(WALLET_MAX_SIZE as u64).deserialize(&buf)?;
-    ensure!(wf.header.version == WALLET_VERSION, "wallet version unsupported (have {}, want {})", wf.header.version, WALLET_VERSION);
+    ensure!((WALLET_VERSION_MIN..=WALLET_VERSION).contains(&wf.header.version),
+        "wallet version unsupported (have {}, want {}..={})", wf.header.version, WALLET_VERSION_MIN, WALLET_VERSION);
     Ok(wf)
 }
 
-fn load_keyset(path: PathBuf) -> Result<Keyset> {
-    let wf = load_wallet_file(&path)?;
-    let pw = Zeroizing::new(prompt_password("Password: ")?);
-    let secret = decrypt_wallet(&wf.enc, pw.as_str(), &wf.header)?;
-    let ks = Keyset::from_master(&secret.master32);
+fn load_keyset_at(path: PathBuf, account: u32, index: u32) -> Result<Keyset> {
+    let secret = load_secret(&path)?;
+    let ks = Keyset::derive(&secret.master32, account, index);
     Ok(ks)
 }
 
+/// Prompt for the password and decrypt the wallet's secret payload
+fn load_secret(path: &Path) -> Result<WalletSecretPayloadV3> {
+    let wf = load_wallet_file(path)?;
+    let pw = Zeroizing::new(prompt_password("Password: ")?);
+    decrypt_wallet(&wf.enc, pw.as_str(), &wf.header)
+}
+
 fn load_keyindex(dir: &Path) -> Result<KeyIndex> {
     KeyIndex::load_latest(dir).context("load latest KeyIndex")
 }
@@ -573,54 +1109,127 @@ fn shards_create(master32: [u8;32], wallet_id: [u8;16], m: u8, n: u8, pw_opt: Op
     Ok(out)
 }
 
+fn shard_mac_ok(sf: &ShardFile) -> Result<bool> {
+    let hdr_bytes = bincode::serialize(&sf.hdr)?;
+    let mut mac_input = hdr_bytes.clone(); mac_input.extend(&sf.share_ct);
+    let mac_chk = ck::kmac256_tag(&shard_mac_key(&sf.hdr.wallet_id, &sf.hdr.salt32), b"TT-SHARD.mac", &mac_input);
+    Ok(mac_chk == sf.mac32)
+}
+
+fn check_shard_header(h: &ShardHeader) -> Result<()> {
+    ensure!(h.version == 1, "unsupported shard version {}", h.version);
+    ensure!(h.scheme == "shamir-gf256" && h.info == "TT-SHARD.v1", "unknown shard scheme");
+    ensure!(h.m >= SHAMIR_MIN_M && h.n >= h.m && h.n <= SHAMIR_MAX_N,
+        "invalid scheme in shard: m={}, n={}", h.m, h.n);
+    ensure!(h.idx >= 1 && h.idx <= h.n, "shard index {} out of range 1..={}", h.idx, h.n);
+    Ok(())
+}
+
+/// Parse one shard file and check MAC + metadata (does not touch the secret)
+fn read_shard(p: &Path) -> Result<ShardFile> {
+    let bytes = fs::read(p).with_context(|| format!("read shard {}", p.display()))?;
+    let sf: ShardFile = serde_json::from_slice(&bytes)
+        .or_else(|_| bincode::deserialize(&bytes))
+        .with_context(|| format!("parse shard {}", p.display()))?;
+    ensure!(shard_mac_ok(&sf)?, "shard MAC mismatch: {}", p.display());
+    check_shard_header(&sf.hdr).with_context(|| format!("shard {}", p.display()))?;
+    Ok(sf)
+}
+
+/// Shards collected so far; all must belong to one wallet and one m-of-n scheme
+#[derive(Default)]
+struct ShardSet {
+    shards: Vec<(ShardHeader, Vec<u8>)>,
+}
+
+impl ShardSet {
+    fn add(&mut self, sf: ShardFile) -> Result<()> {
+        if let Some((first, _)) = self.shards.first() {
+            ensure!(sf.hdr.wallet_id == first.wallet_id && sf.hdr.m == first.m && sf.hdr.n == first.n,
+                "shard #{} mismatch: wallet_id or scheme differs", sf.hdr.idx);
+            ensure!(self.shards.iter().all(|(h, _)| h.idx != sf.hdr.idx), "duplicate shard #{}", sf.hdr.idx);
+        }
+        self.shards.push((sf.hdr, sf.share_ct));
+        Ok(())
+    }
+
+    #[inline]
+    fn len(&self) -> usize { self.shards.len() }
+
+    /// (m, n) of the set, None until the first shard is added
+    fn scheme(&self) -> Option<(u8, u8)> {
+        self.shards.first().map(|(h, _)| (h.m, h.n))
+    }
+
+    /// How many more shards are needed (None = unknown, no shard yet)
+    fn missing(&self) -> Option<usize> {
+        self.scheme().map(|(m, _)| (m as usize).saturating_sub(self.len()))
+    }
+
+    fn recover(self) -> Result<[u8;32]> {
+        let (m, n) = self.scheme().ok_or_else(|| anyhow!("no shard files provided"))?;
+        ensure!(self.len() >= m as usize,
+            "need at least {} shards for {}-of-{} scheme, got {}",
+            m, m, n, self.len());
+
+        // Possibly password unmask
+        let mut rec: Vec<(u8, Zeroizing<Vec<u8>>)> = Vec::new();
+        for (h, ct) in self.shards {
+            let pt = if h.has_pw {
+                let pw = Zeroizing::new(prompt_password(format!("Password for shard #{}: ", h.idx))?);
+                Zeroizing::new(shard_mask(&ct, pw.as_str(), &h.salt32))
+            } else { Zeroizing::new(ct) };
+            rec.push((h.idx, pt));
+        }
+
+        let sharks = Sharks(m as usize);
+        let shares_iter = rec.into_iter().map(|(i, bytes)| Share::new(i, &bytes));
+        let secret = Zeroizing::new(sharks.recover(shares_iter)
+            .map_err(|e| anyhow!("Shamir recovery failed: {}. Ensure you have at least {} valid shards.", e, m))?);
+        let mut out=[0u8;32]; 
+        out.copy_from_slice(&secret);
+        Ok(out)
+    }
+}
+
 fn shards_recover(paths: &[PathBuf]) -> Result<[u8;32]> {
     ensure!(!paths.is_empty(), "no shard files provided");
-    
-    let mut shards: Vec<(ShardHeader, Vec<u8>)> = Vec::new();
+    let mut set = ShardSet::default();
     for p in paths {
-        let bytes = fs::read(p).with_context(|| format!("read shard {}", p.display()))?;
-        let sf: ShardFile = serde_json::from_slice(&bytes)
-            .or_else(|_| bincode::deserialize(&bytes))
-            .with_context(|| format!("parse shard {}", p.display()))?;
-        // MAC verify
-        let hdr_bytes = bincode::serialize(&sf.hdr)?;
-        let mut mac_input = hdr_bytes.clone(); mac_input.extend(&sf.share_ct);
-        let mac_chk = ck::kmac256_tag(&shard_mac_key(&sf.hdr.wallet_id, &sf.hdr.salt32), b"TT-SHARD.mac", &mac_input);
-        ensure!(mac_chk == sf.mac32, "shard MAC mismatch: {}", p.display());
-        shards.push((sf.hdr, sf.share_ct));
+        set.add(read_shard(p)?).with_context(|| format!("shard {}", p.display()))?;
     }
-    
-    // Consistency check
-    let (wid, m, n) = (shards[0].0.wallet_id, shards[0].0.m, shards[0].0.n);
-    ensure!(m >= SHAMIR_MIN_M && n >= m && n <= SHAMIR_MAX_N,
-        "invalid scheme in shards: m={}, n={}", m, n);
-    
-    for (i, (h,_)) in shards.iter().enumerate() {
-        ensure!(h.wallet_id == wid && h.m == m && h.n == n, 
-            "shard #{} mismatch: wallet_id or scheme differs", i+1);
-    }
-    
-    ensure!(shards.len() >= m as usize,
-        "need at least {} shards for {}-of-{} scheme, got {}",
-        m, m, n, shards.len());
+    set.recover()
+}
 
-    // Possibly password unmask
-    let mut rec: Vec<(u8, Vec<u8>)> = Vec::new();
-    for (h, ct) in shards {
-        let pt = if h.has_pw {
-            let pw = Zeroizing::new(prompt_password(format!("Password for shard #{}: ", h.idx))?);
-            shard_mask(&ct, pw.as_str(), &h.salt32)
-        } else { ct };
-        rec.push((h.idx, pt));
-    }
+/// Accept shard paths one per line on stdin until the threshold is reached.
+/// A bad shard is reported and skipped instead of aborting the recovery.
+fn shards_recover_interactive(preload: &[PathBuf]) -> Result<[u8;32]> {
+    let mut set = ShardSet::default();
+    let try_add = |set: &mut ShardSet, p: &Path| {
+        match read_shard(p).and_then(|sf| set.add(sf)) {
+            Ok(()) => {
+                let (m, n) = set.scheme().unwrap_or((0, 0));
+                eprintln!("✓ accepted {} ({}/{} of {}-of-{})", p.display(), set.len(), m, m, n);
+            }
+            Err(e) => eprintln!("✗ rejected {}: {:#}", p.display(), e),
+        }
+    };
+    for p in preload { try_add(&mut set, p); }
 
-    let sharks = Sharks(m as usize);
-    let shares_iter = rec.into_iter().map(|(i, bytes)| Share::new(i, &bytes));
-    let secret = sharks.recover(shares_iter)
-        .map_err(|e| anyhow!("Shamir recovery failed: {}. Ensure you have at least {} valid shards.", e, m))?;
-    let mut out=[0u8;32]; 
-    out.copy_from_slice(&secret);
-    Ok(out)
+    let stdin = std::io::stdin();
+    while set.missing() != Some(0) {
+        match set.missing() {
+            Some(k) => eprint!("shard path ({} more needed, empty line to abort): ", k),
+            None => eprint!("shard path (empty line to abort): "),
+        }
+        std::io::stderr().flush()?;
+        let mut line = String::new();
+        if stdin.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            bail!("recovery aborted with {} shard(s) collected", set.len());
+        }
+        try_add(&mut set, Path::new(line.trim()));
+    }
+    set.recover()
 }
 
 /* =========================================================================================
@@ -643,16 +1252,7 @@ fn create_wallet_header(
     };
 
     let kdf = if use_argon2 {
-        let mut salt32 = [0u8; 32]; OsRng.fill_bytes(&mut salt32);
-        KdfHeader { 
-            kind: KdfKind::Argon2idV1 { 
-                mem_kib: ARGON2_MEM_KIB, 
-                time_cost: ARGON2_TIME_COST, 
-                lanes: ARGON2_LANES, 
-                salt32 
-            }, 
-            info: format!("TT-KDF.v4.argon2id.t{}.m{}MiB.l{}", ARGON2_TIME_COST, ARGON2_MEM_KIB/1024, ARGON2_LANES) 
-        }
+        argon2_kdf_header()
     } else {
         let mut salt32 = [0u8; 32]; OsRng.fill_bytes(&mut salt32);
         KdfHeader { kind: KdfKind::Kmac256V1 { salt32 }, info: "TT-KDF.v4.kmac".into() }
@@ -680,6 +1280,20 @@ fn create_wallet_header(
     })
 }
 
+/// Argon2id with the baseline cost and a fresh salt
+fn argon2_kdf_header() -> KdfHeader {
+    let mut salt32 = [0u8; 32]; OsRng.fill_bytes(&mut salt32);
+    KdfHeader { 
+        kind: KdfKind::Argon2idV1 { 
+            mem_kib: ARGON2_MEM_KIB, 
+            time_cost: ARGON2_TIME_COST, 
+            lanes: ARGON2_LANES, 
+            salt32 
+        }, 
+        info: format!("TT-KDF.v4.argon2id.t{}.m{}MiB.l{}", ARGON2_TIME_COST, ARGON2_MEM_KIB/1024, ARGON2_LANES) 
+    }
+}
+
 fn prompt_and_validate_password() -> Result<Zeroizing<String>> {
     let pw1 = Zeroizing::new(prompt_password(format!("New password (min {} chars): ", MIN_PASSWORD_LEN))?);
     ensure!(pw1.len() >= MIN_PASSWORD_LEN, 
@@ -689,6 +1303,19 @@ fn prompt_and_validate_password() -> Result<Zeroizing<String>> {
     Ok(pw1)
 }
 
+/// Re-encrypt an updated payload under the same password/KDF with fresh nonces
+/// (XChaCha20 must never reuse a nonce for a different plaintext).
+fn rewrite_wallet(path: &Path, wf: &WalletFile, password: &str, payload: &WalletSecretPayloadV3) -> Result<()> {
+    let mut hdr = wf.header.clone();
+    hdr.version = WALLET_VERSION;
+    OsRng.fill_bytes(&mut hdr.nonce12);
+    if let Some(n24) = hdr.nonce24_opt.as_mut() { OsRng.fill_bytes(n24); }
+    let enc = encrypt_wallet(payload, password, &hdr)?;
+    let wf2 = WalletFile { header: hdr, enc };
+    let bytes = bincode::options().with_limit(WALLET_MAX_SIZE).serialize(&wf2)?;
+    atomic_replace(path, &bytes)
+}
+
 /* =========================================================================================
  * Commands impl
  * ====================================================================================== */
@@ -701,7 +1328,7 @@ fn cmd_wallet_init(path: PathBuf, use_argon2: bool, aead_flag: AeadFlag, pepper_
 
     // Random master
     let mut master32 = [0u8; 32]; OsRng.fill_bytes(&mut master32);
-    let payload = WalletSecretPayloadV2 { master32 };
+    let payload = WalletSecretPayloadV3::new(master32);
 
     let enc = encrypt_wallet(&payload, pw.as_str(), &hdr)?;
 
@@ -713,9 +1340,14 @@ fn cmd_wallet_init(path: PathBuf, use_argon2: bool, aead_flag: AeadFlag, pepper_
     Ok(())
 }
 
-fn cmd_wallet_addr(path: PathBuf) -> Result<()> {
-    let ks = load_keyset(path)?;
-    let addr = bech32_addr(&ks.scan_pk, &ks.spend_pk)?;
+fn cmd_wallet_addr(path: PathBuf, account: u32, index: u32, diversifier: Option<u32>) -> Result<()> {
+    let mut ks = load_keyset_at(path, account, index)?;
+    let addr = match diversifier {
+        Some(d) => { ks = ks.diversified(d); bech32_div_addr(d, &ks.scan_pk, &ks.spend_pk)? }
+        None => bech32_addr(&ks.scan_pk, &ks.spend_pk)?,
+    };
+    println!("path: account={} index={}", account, index);
+    if let Some(d) = diversifier { println!("diversifier: {}", d); }
     println!("address: {}", addr);
     println!("scan_pk (x25519): {}", hex::encode(ks.scan_pk.as_bytes()));
     println!("spend_pk(ed25519): {}", hex::encode(ks.spend_pk.to_bytes()));
@@ -731,12 +1363,12 @@ fn cmd_wallet_export(path: PathBuf, secret: bool, out: Option<PathBuf>) -> Resul
         let outp = out.ok_or_else(|| anyhow!("secret export requires --out <file> (STDOUT disabled)"))?;
         let confirm = Zeroizing::new(prompt_password("Type wallet password again to CONFIRM secret export: ")?);
         let _ = decrypt_wallet(&wf.enc, confirm.as_str(), &wf.header)?;
-        let txt = format!(
+        let txt = Zeroizing::new(format!(
             "{{\"master32\":\"{}\",\"scan_sk\":\"{}\",\"spend_sk\":\"{}\"}}\n",
             hex::encode(secret_payload.master32),
             hex::encode(ks.scan_sk.to_bytes()),
             hex::encode(ks.spend_sk.to_bytes()),
-        );
+        ));
         atomic_write(&outp, txt.as_bytes())?;
         eprintln!("🔒 secrets written → {}", outp.display());
     } else {
@@ -746,6 +1378,73 @@ fn cmd_wallet_export(path: PathBuf, secret: bool, out: Option<PathBuf>) -> Resul
     Ok(())
 }
 
+fn cmd_wallet_export_view(path: PathBuf, out: PathBuf, account: u32, index: u32) -> Result<()> {
+    let ks = load_keyset_at(path, account, index)?;
+    let vk = ViewKeyFile::from_keyset(&ks)?;
+    let txt = Zeroizing::new(serde_json::to_string_pretty(&vk)? + "\n");
+    atomic_write(&out, txt.as_bytes())?;
+    eprintln!("👁  view key written → {} (account={} index={}, cannot spend)", out.display(), account, index);
+    println!("address: {}", vk.address);
+    Ok(())
+}
+
+fn cmd_wallet_backup(path: PathBuf, target: PathBuf) -> Result<()> {
+    let wf = load_wallet_file(&path)?;
+    let pw = Zeroizing::new(prompt_password("Password: ")?);
+    let secret = decrypt_wallet(&wf.enc, pw.as_str(), &wf.header)?;
+    eprintln!("Backup password (independent of the wallet password and pepper):");
+    let bpw = prompt_and_validate_password()?;
+    let pt = Zeroizing::new(bincode::options().with_limit(WALLET_MAX_SIZE).serialize(&secret)?);
+    let backup = seal_backup(&pt, WALLET_VERSION, wf.header.wallet_id, bpw.as_str(), argon2_kdf_header())?;
+    let out = backup_target_path(&target, &backup.header)?;
+    let bytes = bincode::options().with_limit(WALLET_MAX_SIZE).serialize(&backup)?;
+    atomic_write(&out, &bytes)?;
+    eprintln!("💾 backup written → {} (wallet v{}, id {})", out.display(), WALLET_VERSION, hex::encode(wf.header.wallet_id));
+    Ok(())
+}
+
+fn cmd_wallet_restore(backup: PathBuf, path: PathBuf, force: bool, use_argon2: bool, aead_flag: AeadFlag, pepper_flag: PepperFlag, pad_block: u16) -> Result<()> {
+    if path.exists() && !force { bail!("file exists: {} (use --force to overwrite)", path.display()); }
+    let b = load_backup(&backup)?;
+    let bpw = Zeroizing::new(prompt_password("Backup password: ")?);
+    let payload = open_backup(&b, bpw.as_str())?;
+    if b.header.wallet_version != WALLET_VERSION {
+        eprintln!("🔧 backup payload migrated v{} → v{}", b.header.wallet_version, WALLET_VERSION);
+    }
+    let pw = prompt_and_validate_password()?;
+    let hdr = create_wallet_header(use_argon2, aead_flag, pepper_flag, pad_block, Some(b.header.wallet_id))?;
+    let enc = encrypt_wallet(&payload, pw.as_str(), &hdr)?;
+    let bytes = bincode::options().with_limit(WALLET_MAX_SIZE).serialize(&WalletFile { header: hdr, enc })?;
+    if path.exists() { atomic_replace(&path, &bytes)?; } else { atomic_write(&path, &bytes)?; }
+    eprintln!("✅ restored wallet: {} (id {})", path.display(), hex::encode(b.header.wallet_id));
+    Ok(())
+}
+
+fn cmd_wallet_migrate(path: PathBuf, dry_run: bool) -> Result<()> {
+    let wf = load_wallet_file(&path)?;
+    let from = wf.header.version;
+    if from == WALLET_VERSION {
+        println!("wallet already at v{}: {}", WALLET_VERSION, path.display());
+        return Ok(());
+    }
+    let pw = Zeroizing::new(prompt_password("Password: ")?);
+    let secret = decrypt_wallet(&wf.enc, pw.as_str(), &wf.header)?;
+    // plan (decrypt_wallet already verified that the chain applies)
+    for m in MIGRATIONS.iter().filter(|m| m.from >= from && m.from < WALLET_VERSION) {
+        println!("step: {}", m.describe);
+    }
+    if dry_run {
+        println!("dry run: {} not modified", path.display());
+        return Ok(());
+    }
+    let backup = migration_backup_path(&path, from);
+    let orig = fs::read(&path).with_context(|| format!("read {}", path.display()))?;
+    atomic_write(&backup, &orig).with_context(|| format!("backup {}", backup.display()))?;
+    rewrite_wallet(&path, &wf, pw.as_str(), &secret)?;
+    eprintln!("✅ migrated wallet v{} -> v{}: {} (backup: {})", from, WALLET_VERSION, path.display(), backup.display());
+    Ok(())
+}
+
 fn cmd_wallet_rekey(path: PathBuf, use_argon2: bool, aead_flag: AeadFlag, pepper_flag: PepperFlag, pad_block: u16) -> Result<()> {
     let wf = load_wallet_file(&path)?;
     let old_pw = Zeroizing::new(prompt_password("Old password: ")?);
@@ -838,15 +1537,80 @@ fn hex16(s: &str) -> Result<[u8;16]> {
 }
 fn hexv(s: &str) -> Result<Vec<u8>> { Ok(hex::decode(s.trim())?) }
 
-fn cmd_keysearch_pairs(wallet: PathBuf, file: PathBuf) -> Result<()> {
-    use pot80_zk_host::keysearch::{KeySearchCtx, AadMode, MAX_ENC_HINT_BYTES};
+/// `--net-id` or, when absent, the `TT_NET_ID` environment variable
+fn resolve_net_id(flag: Option<u32>) -> Result<Option<u32>> {
+    if flag.is_some() { return Ok(flag); }
+    match std::env::var("TT_NET_ID") {
+        Ok(v) if !v.trim().is_empty() => Ok(Some(v.trim().parse().context("TT_NET_ID must be a u32")?)),
+        _ => Ok(None),
+    }
+}
+
+/// eph_pub ‖ tag16: a hint without an encrypted payload
+const TAG_ONLY_HINT_BYTES: usize = 48;
+
+/// Decrypted hint fields, formatted as keysearch-pairs prints them
+#[derive(Debug, PartialEq, Eq)]
+struct HintPayload {
+    value: String,
+    tlv_items: usize,
+    r_blind: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct PairHit {
+    label: String,
+    k_search: [u8; 32],
+    payload: Option<HintPayload>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum PairMatch {
+    Hit(PairHit),
+    /// Tag matched, but the payload does not open under the requested net_id
+    WrongNet,
+    Miss,
+}
+
+/// Try one (c_out, enc_hint) pair against every scan key
+fn match_pair(ctxs: &[(String, pot80_zk_host::keysearch::KeySearchCtx)], c_out: &[u8; 32], enc: &[u8], net_id: Option<u32>) -> PairMatch {
+    use pot80_zk_host::keysearch::AadMode;
+    let aad = || match net_id { Some(n) => AadMode::NetIdAndCOut(n), None => AadMode::COutOnly };
+    let found = ctxs.iter()
+        .find_map(|(label, ctx)| ctx.try_match_and_decrypt_ext(c_out, enc, aad()).map(|m| (label, m)));
+    let Some((label, (k_search, maybe))) = found else { return PairMatch::Miss };
+    // tag pasuje, ale AAD nie: hint z innej sieci albo bez wiązania
+    if maybe.is_none() && net_id.is_some() && enc.len() > TAG_ONLY_HINT_BYTES {
+        return PairMatch::WrongNet;
+    }
+    PairMatch::Hit(PairHit {
+        label: label.clone(),
+        k_search,
+        payload: maybe.map(|d| HintPayload {
+            value: format!("{:?}", d.value),
+            tlv_items: d.memo_items.len(),
+            r_blind: hex::encode(d.r_blind),
+        }),
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn cmd_keysearch_pairs(
+    wallet: Option<PathBuf>,
+    view: Option<PathBuf>,
+    file: PathBuf,
+    div_window: u32,
+    account: u32,
+    index_window: u32,
+    net_id: Option<u32>,
+) -> Result<()> {
+    use pot80_zk_host::keysearch::MAX_ENC_HINT_BYTES;
 
-    let ks = load_keyset(wallet)?;
-    let view = ks.scan_sk.to_bytes();
-    let ctx = KeySearchCtx::new(view);
+    let net_id = resolve_net_id(net_id)?;
+    let ctxs = keysearch_ctxs(load_scan_secrets(wallet, view, account, index_window)?, div_window)?;
 
     let txt = fs::read_to_string(&file).with_context(|| format!("read {}", file.display()))?;
-    let mut hits = 0usize; let mut total = 0usize;
+    let mut hits = 0usize; let mut total = 0usize; let mut rejected = 0usize;
 
     for (lineno, line) in txt.lines().enumerate() {
         if line.trim().is_empty() { continue; }
@@ -862,37 +1626,37 @@ fn cmd_keysearch_pairs(wallet: PathBuf, file: PathBuf) -> Result<()> {
             continue;
         }
 
-        if let Some((k, maybe)) = ctx.try_match_and_decrypt_ext(&c_out, &enc, AadMode::COutOnly) {
-            match maybe {
-                Some(dec) => {
-                    println!(
-                        "hit #{}: c_out={} k_search={} value={:?} tlv_items={} r_blind={}",
-                        total,
-                        rec.c_out,
-                        hex::encode(k),
-                        dec.value,
-                        dec.memo_items.len(),
-                        hex::encode(dec.r_blind)
-                    );
-                }
-                None => {
-                    println!(
-                        "hit #{}: c_out={} k_search={} (no payload)",
-                        total, rec.c_out, hex::encode(k)
-                    );
+        match match_pair(&ctxs, &c_out, &enc, net_id) {
+            PairMatch::Hit(h) => {
+                match h.payload {
+                    Some(p) => println!(
+                        "hit #{}: c_out={} k_search={} value={} tlv_items={} r_blind={}{}",
+                        total, rec.c_out, hex::encode(h.k_search), p.value, p.tlv_items, p.r_blind, h.label
+                    ),
+                    None => println!(
+                        "hit #{}: c_out={} k_search={} (no payload){}",
+                        total, rec.c_out, hex::encode(h.k_search), h.label
+                    ),
                 }
+                hits += 1;
             }
-            hits += 1;
+            PairMatch::WrongNet => {
+                eprintln!("reject line {}: enc_hint not bound to net_id {}", lineno+1, net_id.unwrap_or_default());
+                rejected += 1;
+            }
+            PairMatch::Miss => {}
         }
     }
 
-    println!("total: {} hits: {}", total, hits);
+    match net_id {
+        Some(n) => println!("total: {} hits: {} rejected(net_id != {}): {}", total, hits, n, rejected),
+        None => println!("total: {} hits: {}", total, hits),
+    }
     Ok(())
 }
 
-fn cmd_keysearch_stateless(wallet: PathBuf, file: PathBuf) -> Result<()> {
-    let ks = load_keyset(wallet)?; let view = ks.scan_sk.to_bytes();
-    let ctx = pot80_zk_host::keysearch::KeySearchCtx::new(view);
+fn cmd_keysearch_stateless(wallet: Option<PathBuf>, view: Option<PathBuf>, file: PathBuf, div_window: u32, account: u32, index_window: u32) -> Result<()> {
+    let ctxs = keysearch_ctxs(load_scan_secrets(wallet, view, account, index_window)?, div_window)?;
 
     let txt = fs::read_to_string(&file).with_context(|| format!("read {}", file.display()))?;
     let mut hits = 0usize; let mut total = 0usize;
@@ -900,8 +1664,8 @@ fn cmd_keysearch_stateless(wallet: PathBuf, file: PathBuf) -> Result<()> {
         if line.trim().is_empty() { continue; }
         let rec: PairStateless = serde_json::from_str(line).with_context(|| format!("jsonl line {}", lineno+1))?;
         let c_out = hex32(&rec.c_out)?; let eph = hex32(&rec.eph_pub)?; let h = hex32(&rec.enc_hint32)?; total += 1;
-        if let Some(k) = ctx.try_match_stateless(&c_out, &eph, &h) {
-            println!("hit #{}: c_out={} k_search={}", total, rec.c_out, hex::encode(k));
+        if let Some((label, k)) = ctxs.iter().find_map(|(label, ctx)| ctx.try_match_stateless(&c_out, &eph, &h).map(|k| (label, k))) {
+            println!("hit #{}: c_out={} k_search={}{}", total, rec.c_out, hex::encode(k), label);
             hits += 1;
         }
     }
@@ -909,9 +1673,8 @@ fn cmd_keysearch_stateless(wallet: PathBuf, file: PathBuf) -> Result<()> {
     Ok(())
 }
 
-fn cmd_keysearch_header(wallet: PathBuf, file: PathBuf) -> Result<()> {
-    let ks = load_keyset(wallet)?; let view = ks.scan_sk.to_bytes();
-    let ctx = pot80_zk_host::keysearch::KeySearchCtx::new(view);
+fn cmd_keysearch_header(wallet: Option<PathBuf>, view: Option<PathBuf>, file: PathBuf, div_window: u32, account: u32, index_window: u32) -> Result<()> {
+    let ctxs = keysearch_ctxs(load_scan_secrets(wallet, view, account, index_window)?, div_window)?;
 
     let txt = fs::read_to_string(&file).with_context(|| format!("read {}", file.display()))?;
     let mut hits = 0usize; let mut total = 0usize;
@@ -919,8 +1682,8 @@ fn cmd_keysearch_header(wallet: PathBuf, file: PathBuf) -> Result<()> {
         if line.trim().is_empty() { continue; }
         let rec: PairHeader = serde_json::from_str(line).with_context(|| format!("jsonl line {}", lineno+1))?;
         let eph = hex32(&rec.eph_pub)?; let t16 = hex16(&rec.hdr_tag16)?; total += 1;
-        if ctx.header_hit(&eph, &t16) {
-            println!("prefilter hit #{}: eph_pub={}", total, rec.eph_pub);
+        if let Some((label, _)) = ctxs.iter().find(|(_, ctx)| ctx.header_hit(&eph, &t16)) {
+            println!("prefilter hit #{}: eph_pub={}{}", total, rec.eph_pub, label);
             hits += 1;
         }
     }
@@ -947,7 +1710,7 @@ fn cmd_build_enc_hint(
 
     let r_blind = if let Some(h) = r_blind_hex { hex32(&h)? } else { let mut r=[0u8;32]; OsRng.fill_bytes(&mut r); r };
 
-    let aad_mode = if let Some(n) = net_id { AadMode::NetIdAndCOut(n) } else { AadMode::COutOnly };
+    let aad_mode = if let Some(n) = resolve_net_id(net_id)? { AadMode::NetIdAndCOut(n) } else { AadMode::COutOnly };
 
     let val_mode = match (value, mask_value) {
         (Some(v), true)  => ValueConceal::Masked(v),
@@ -973,6 +1736,73 @@ fn cmd_build_enc_hint(
     Ok(())
 }
 
+/* =========================================================================================
+ * Contacts / payment URI commands
+ * ====================================================================================== */
+
+fn cmd_contacts_add(file: PathBuf, label: String, address: String) -> Result<()> {
+    let label = label.trim().to_string();
+    ensure!(!label.is_empty() && label.len() <= CONTACT_LABEL_MAX,
+        "contact label must be 1..={} bytes", CONTACT_LABEL_MAX);
+    parse_bech32_addr(&address)?;
+
+    let pw = Zeroizing::new(prompt_password("Password: ")?);
+    add_contact(&file, pw.as_str(), &label, &address)?;
+    eprintln!("📇 added contact '{}'", label);
+    Ok(())
+}
+
+fn add_contact(file: &Path, password: &str, label: &str, address: &str) -> Result<()> {
+    let wf = load_wallet_file(file)?;
+    let mut secret = decrypt_wallet(&wf.enc, password, &wf.header)?;
+    ensure!(!secret.contacts.iter().any(|c| c.label == label), "contact '{}' already exists", label);
+    secret.contacts.push(Contact { label: label.to_string(), address: address.trim().to_string() });
+    rewrite_wallet(file, &wf, password, &secret)
+}
+
+fn cmd_contacts_list(file: PathBuf) -> Result<()> {
+    let wf = load_wallet_file(&file)?;
+    let pw = Zeroizing::new(prompt_password("Password: ")?);
+    let secret = decrypt_wallet(&wf.enc, pw.as_str(), &wf.header)?;
+    for c in &secret.contacts {
+        println!("{}\t{}", c.label, c.address);
+    }
+    println!("contacts: {}", secret.contacts.len());
+    Ok(())
+}
+
+fn cmd_contacts_remove(file: PathBuf, label: String) -> Result<()> {
+    let pw = Zeroizing::new(prompt_password("Password: ")?);
+    remove_contact(&file, pw.as_str(), label.trim())?;
+    eprintln!("🗑️ removed contact '{}'", label.trim());
+    Ok(())
+}
+
+fn remove_contact(file: &Path, password: &str, label: &str) -> Result<()> {
+    let wf = load_wallet_file(file)?;
+    let mut secret = decrypt_wallet(&wf.enc, password, &wf.header)?;
+    let before = secret.contacts.len();
+    secret.contacts.retain(|c| c.label != label);
+    ensure!(secret.contacts.len() < before, "no contact named '{}'", label);
+    rewrite_wallet(file, &wf, password, &secret)
+}
+
+fn cmd_pay_uri_build(address: String, amount: Option<u64>, memo: Option<String>) -> Result<()> {
+    parse_bech32_addr(&address)?;
+    let req = PaymentRequest { address: address.trim().to_string(), amount, memo };
+    println!("{}", req.to_uri());
+    Ok(())
+}
+
+fn cmd_pay_uri_parse(uri: String) -> Result<()> {
+    let req = PaymentRequest::parse(&uri)?;
+    println!("address: {}", req.address);
+    if let (_, _, Some(d)) = parse_bech32_addr_ext(&req.address)? { println!("diversifier: {}", d); }
+    match req.amount { Some(a) => println!("amount: {}", a), None => println!("amount: (unspecified)") }
+    if let Some(m) = req.memo { println!("memo: {}", m); }
+    Ok(())
+}
+
 /* =========================================================================================
  * Shards Commands
  * ====================================================================================== */
@@ -1001,11 +1831,12 @@ fn cmd_shards_create(file: PathBuf, out_dir: PathBuf, m:u8, n:u8, per_share_pass
     Ok(())
 }
 
-fn cmd_shards_recover(input: Vec<PathBuf>, out: PathBuf, use_argon2: bool, aead_flag: AeadFlag, pepper_flag: PepperFlag, pad_block: u16) -> Result<()> {
-    let master32 = shards_recover(&input)?;
+fn cmd_shards_recover(input: Vec<PathBuf>, out: PathBuf, interactive: bool, use_argon2: bool, aead_flag: AeadFlag, pepper_flag: PepperFlag, pad_block: u16) -> Result<()> {
+    if out.exists() { bail!("file exists: {}", out.display()); }
+    let master32 = if interactive { shards_recover_interactive(&input)? } else { shards_recover(&input)? };
     let pw = prompt_and_validate_password()?;
     let hdr = create_wallet_header(use_argon2, aead_flag, pepper_flag, pad_block, None)?;
-    let payload = WalletSecretPayloadV2 { master32 };
+    let payload = WalletSecretPayloadV3::new(master32);
     let enc = encrypt_wallet(&payload, pw.as_str(), &hdr)?;
     let wf = WalletFile { header: hdr, enc };
     let bytes = bincode::options().with_limit(WALLET_MAX_SIZE as u64).serialize(&wf)?;
@@ -1014,6 +1845,75 @@ fn cmd_shards_recover(input: Vec<PathBuf>, out: PathBuf, use_argon2: bool, aead_
     Ok(())
 }
 
+fn cmd_shards_verify(input: Vec<PathBuf>) -> Result<()> {
+    let mut set = ShardSet::default();
+    let mut bad = 0usize;
+    for p in &input {
+        let res = read_shard(p).and_then(|sf| {
+            let line = format!("idx={}/{} m={} wallet_id={} pw={}",
+                sf.hdr.idx, sf.hdr.n, sf.hdr.m, hex::encode(sf.hdr.wallet_id), if sf.hdr.has_pw { "yes" } else { "no" });
+            set.add(sf)?;
+            Ok(line)
+        });
+        match res {
+            Ok(line) => println!("ok   {}: {}", p.display(), line),
+            Err(e) => { bad += 1; println!("BAD  {}: {:#}", p.display(), e); }
+        }
+    }
+    if let Some((m, n)) = set.scheme() {
+        match set.missing() {
+            Some(0) => println!("{} valid shard(s): enough for {}-of-{} recovery", set.len(), m, n),
+            Some(k) => println!("{} valid shard(s): {} more needed for {}-of-{} recovery", set.len(), k, m, n),
+            None => {}
+        }
+    }
+    ensure!(bad == 0, "{} of {} shard(s) failed verification", bad, input.len());
+    Ok(())
+}
+
+/* =========================================================================================
+ * Simulation
+ * ====================================================================================== */
+
+#[allow(clippy::too_many_arguments)]
+fn cmd_simulate(honest: usize, equivocators: usize, withholders: usize, invalid: usize, slots: u64, slots_per_epoch: u64, seed: Option<String>, partition: Option<String>) -> Result<()> {
+    use tt_priv_cli::simulation::{Behavior, Partition, SimConfig, Simulation};
+
+    let mut seed32 = [0u8; 32];
+    if let Some(h) = seed {
+        let b = hex::decode(h.trim()).context("seed hex")?;
+        ensure!(b.len() == 32, "seed must be 32 bytes");
+        seed32.copy_from_slice(&b);
+    }
+    let mut behaviors = vec![Behavior::Honest; honest];
+    behaviors.extend(std::iter::repeat_n(Behavior::Equivocate, equivocators));
+    behaviors.extend(std::iter::repeat_n(Behavior::WithholdReveal, withholders));
+    behaviors.extend(std::iter::repeat_n(Behavior::InvalidProof, invalid));
+
+    let mut cfg = SimConfig::new(seed32, slots, behaviors);
+    cfg.slots_per_epoch = slots_per_epoch;
+    if let Some(p) = partition {
+        let (a, b) = p.split_once(':').ok_or_else(|| anyhow!("partition must be FROM:UNTIL"))?;
+        cfg.partition = Some(Partition {
+            from_slot: a.trim().parse().context("partition FROM")?,
+            until_slot: b.trim().parse().context("partition UNTIL")?,
+            split: cfg.behaviors.len() / 2,
+        });
+    }
+
+    let report = Simulation::new(cfg).map_err(|e| anyhow!(e))?.run();
+    println!("slots: {}  proposed: {}  rejected deliveries: {}", slots, report.proposed, report.rejected);
+    for (i, (h, height)) in report.heads.iter().zip(&report.head_heights).enumerate() {
+        println!("node {:>2}: head={} height={}", i, hex::encode(&h[..8]), height);
+    }
+    for who in &report.equivocators { println!("equivocation detected: {}", hex::encode(&who[..8])); }
+    for who in &report.slashed_noreveal { println!("slashed (no reveal): {}", hex::encode(&who[..8])); }
+    for v in &report.violations { println!("VIOLATION: {}", v); }
+    ensure!(report.violations.is_empty(), "{} invariant violation(s)", report.violations.len());
+    eprintln!("✅ all invariants hold");
+    Ok(())
+}
+
 /* =========================================================================================
  * main
  * ====================================================================================== */
@@ -1024,9 +1924,15 @@ fn main() -> Result<()> {
         Cmd::WalletInit { file, argon2, aead, pepper, pad_block } =>
             cmd_wallet_init(file, argon2, aead, pepper, pad_block)?,
 
-        Cmd::WalletAddr { file } => cmd_wallet_addr(file)?,
+        Cmd::WalletAddr { file, account, index, diversifier } => cmd_wallet_addr(file, account, index, diversifier)?,
 
         Cmd::WalletExport { file, secret, out } => cmd_wallet_export(file, secret, out)?,
+        Cmd::WalletExportView { file, out, account, index } => cmd_wallet_export_view(file, out, account, index)?,
+
+        Cmd::WalletMigrate { file, dry_run } => cmd_wallet_migrate(file, dry_run)?,
+        Cmd::WalletBackup { file, target } => cmd_wallet_backup(file, target)?,
+        Cmd::WalletRestore { backup, file, force, argon2, aead, pepper, pad_block } =>
+            cmd_wallet_restore(backup, file, force, argon2, aead, pepper, pad_block)?,
 
         Cmd::WalletRekey { file, argon2, aead, pepper, pad_block } =>
             cmd_wallet_rekey(file, argon2, aead, pepper, pad_block)?,
@@ -1036,9 +1942,12 @@ fn main() -> Result<()> {
         Cmd::ScanDir { filters, dir } => cmd_scan_dir(filters, dir)?,
         Cmd::ScanHeader { filters, file } => cmd_scan_header(filters, file)?,
 
-        Cmd::KeysearchPairs { wallet, file } => cmd_keysearch_pairs(wallet, file)?,
-        Cmd::KeysearchStateless { wallet, file } => cmd_keysearch_stateless(wallet, file)?,
-        Cmd::KeysearchHeader { wallet, file } => cmd_keysearch_header(wallet, file)?,
+        Cmd::KeysearchPairs { wallet, view, file, div_window, account, index_window, net_id } =>
+            cmd_keysearch_pairs(wallet, view, file, div_window, account, index_window, net_id)?,
+        Cmd::KeysearchStateless { wallet, view, file, div_window, account, index_window } =>
+            cmd_keysearch_stateless(wallet, view, file, div_window, account, index_window)?,
+        Cmd::KeysearchHeader { wallet, view, file, div_window, account, index_window } =>
+            cmd_keysearch_header(wallet, view, file, div_window, account, index_window)?,
 
         Cmd::BuildEncHint { scan_pk, c_out, r_blind_hex, net_id, value, mask_value, memo_utf8, memo_hex, out } =>
             cmd_build_enc_hint(scan_pk, c_out, r_blind_hex, net_id, value, mask_value, memo_utf8, memo_hex, out)?,
@@ -1046,8 +1955,287 @@ fn main() -> Result<()> {
         Cmd::ShardsCreate { file, out_dir, m, n, per_share_pass } =>
             cmd_shards_create(file, out_dir, m, n, per_share_pass)?,
 
-        Cmd::ShardsRecover { input, out, argon2, aead, pepper, pad_block } =>
-            cmd_shards_recover(input, out, argon2, aead, pepper, pad_block)?,
+        Cmd::ShardsRecover { input, out, interactive, argon2, aead, pepper, pad_block } =>
+            cmd_shards_recover(input, out, interactive, argon2, aead, pepper, pad_block)?,
+        Cmd::ShardsVerify { input } => cmd_shards_verify(input)?,
+
+        Cmd::ContactsAdd { file, label, address } => cmd_contacts_add(file, label, address)?,
+        Cmd::ContactsList { file } => cmd_contacts_list(file)?,
+        Cmd::ContactsRemove { file, label } => cmd_contacts_remove(file, label)?,
+
+        Cmd::PayUriBuild { address, amount, memo } => cmd_pay_uri_build(address, amount, memo)?,
+        Cmd::PayUriParse { uri } => cmd_pay_uri_parse(uri)?,
+
+        Cmd::Simulate { honest, equivocators, withholders, invalid, slots, slots_per_epoch, seed, partition } =>
+            cmd_simulate(honest, equivocators, withholders, invalid, slots, slots_per_epoch, seed, partition)?,
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v4_payload(master32: [u8; 32]) -> Zeroizing<Vec<u8>> {
+        let v4 = WalletSecretPayloadV2 { master32 };
+        Zeroizing::new(bincode::options().with_limit(WALLET_MAX_SIZE).serialize(&v4).unwrap())
+    }
+
+    #[test]
+    fn migrate_v4_to_v5_keeps_master() {
+        let out = migrate_v4_to_v5(&v4_payload([7u8; 32])).unwrap();
+        let v5: WalletSecretPayloadV3 = bincode::options().with_limit(WALLET_MAX_SIZE).deserialize(&out).unwrap();
+        assert_eq!(v5.master32, [7u8; 32]);
+        assert!(v5.contacts.is_empty());
+    }
+
+    #[test]
+    fn migration_chain_covers_every_version() {
+        for v in WALLET_VERSION_MIN..WALLET_VERSION {
+            assert_eq!(MIGRATIONS.iter().filter(|m| m.from == v).count(), 1, "migration from v{v}");
+        }
+        let (_, steps) = migrate_payload(WALLET_VERSION_MIN, v4_payload([1u8; 32])).unwrap();
+        assert_eq!(steps.len() as u32, WALLET_VERSION - WALLET_VERSION_MIN);
+    }
+
+    #[test]
+    fn current_version_is_untouched_and_unknown_rejected() {
+        let v5 = WalletSecretPayloadV3::new([3u8; 32]);
+        let pt = Zeroizing::new(bincode::options().with_limit(WALLET_MAX_SIZE).serialize(&v5).unwrap());
+        let (out, steps) = migrate_payload(WALLET_VERSION, pt.clone()).unwrap();
+        assert!(steps.is_empty());
+        assert_eq!(*out, *pt);
+        assert!(migrate_payload(WALLET_VERSION_MIN - 1, pt.clone()).is_err());
+        assert!(migrate_payload(WALLET_VERSION + 1, pt).is_err());
+        // garbage v4 payload is an error, not a panic
+        assert!(migrate_payload(4, Zeroizing::new(vec![1, 2])).is_err());
+    }
+
+    #[test]
+    fn shard_set_checks_consistency() {
+        let shards = shards_create([9u8; 32], [1u8; 16], 2, 3, None).unwrap();
+        let other = shards_create([9u8; 32], [2u8; 16], 2, 3, None).unwrap();
+        for sf in shards.iter().chain(&other) {
+            assert!(shard_mac_ok(sf).unwrap());
+            check_shard_header(&sf.hdr).unwrap();
+        }
+
+        let mut it = shards.into_iter();
+        let mut set = ShardSet::default();
+        assert_eq!(set.missing(), None);
+        set.add(it.next().unwrap()).unwrap();
+        assert_eq!(set.missing(), Some(1));
+        assert!(set.add(other.into_iter().nth(1).unwrap()).is_err());
+        let mut tampered = it.next().unwrap();
+        tampered.share_ct[0] ^= 1;
+        assert!(!shard_mac_ok(&tampered).unwrap());
+        tampered.share_ct[0] ^= 1;
+        set.add(tampered).unwrap();
+        assert_eq!(set.missing(), Some(0));
+        assert_eq!(set.recover().unwrap(), [9u8; 32]);
+    }
+
+    #[test]
+    fn backup_path_appends_version() {
+        assert_eq!(migration_backup_path(Path::new("w/my.dat"), 4), PathBuf::from("w/my.dat.v4.bak"));
+    }
+
+    #[test]
+    fn diversified_addresses_are_unlinkable_and_scannable() {
+        let base = Keyset::from_master(&[7u8; 32]);
+        let (d1, d2) = (base.diversified(1), base.diversified(2));
+        assert_ne!(d1.scan_pk, d2.scan_pk);
+        assert_ne!(d1.scan_pk, base.scan_pk);
+        assert_ne!(d1.spend_pk, d2.spend_pk);
+        assert_ne!(d1.spend_pk, base.spend_pk);
+
+        let addr = bech32_div_addr(1, &d1.scan_pk, &d1.spend_pk).unwrap();
+        let (scan, spend, div) = parse_bech32_addr_ext(&addr).unwrap();
+        assert_eq!((scan, spend, div), (d1.scan_pk, d1.spend_pk, Some(1)));
+        let plain = bech32_addr(&base.scan_pk, &base.spend_pk).unwrap();
+        assert_eq!(parse_bech32_addr_ext(&plain).unwrap().2, None);
+
+        // okno skanowania z samego scan_sk (view key) obejmuje d1
+        let secrets = scan_secrets(&base.scan_sk.to_bytes(), 3).unwrap();
+        assert_eq!(secrets.len(), 4);
+        assert!(secrets.iter().any(|(d, sk)| *d == Some(1) && **sk == d1.scan_sk.to_bytes()));
+        assert!(scan_secrets(&[0u8; 32], MAX_DIV_WINDOW + 1).is_err());
+    }
+
+    #[test]
+    fn keysearch_finds_payment_to_nonzero_hd_index() {
+        use pot80_zk_host::keysearch::{AadMode, KeySearchCtx, ValueConceal};
+        let master = [5u8; 32];
+        let ks = Keyset::derive(&master, 0, 3);
+        let c_out = [4u8; 32];
+        let enc = KeySearchCtx::build_enc_hint_ext(&ks.scan_pk, &c_out, AadMode::COutOnly, None, ValueConceal::Plain(7), &[]);
+        let hit = |index_window: u32| {
+            let ctxs = keysearch_ctxs(wallet_scan_secrets(&master, 0, index_window).unwrap(), 0).unwrap();
+            ctxs.iter()
+                .find_map(|(label, ctx)| ctx.try_match_and_decrypt_ext(&c_out, &enc, AadMode::COutOnly).map(|(_, dec)| (label.clone(), dec)))
+                .map(|(label, dec)| (label, dec.and_then(|d| d.value)))
+        };
+        // domyślnie tylko (0, 0): płatność na indeks 3 jest niewidoczna
+        assert_eq!(hit(1), None);
+        assert_eq!(hit(3), None);
+        assert_eq!(hit(4), Some((" account=0 index=3".to_string(), Some(7))));
+        assert!(wallet_scan_secrets(&master, 0, 0).is_err());
+        assert!(keysearch_ctxs(wallet_scan_secrets(&master, 0, 4).unwrap(), MAX_DIV_WINDOW).is_err());
+        assert_eq!(wallet_scan_secrets(&master, 0, 1).unwrap()[0].0, "");
+    }
+
+    fn test_addr(seed: u8) -> String {
+        let ks = Keyset::from_master(&[seed; 32]);
+        bech32_addr(&ks.scan_pk, &ks.spend_pk).unwrap()
+    }
+
+    /// Wallet file with a KMAC KDF and no pepper (fast, no OS state)
+    fn test_wallet(name: &str, password: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("tt-test-{}-{}.dat", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        let hdr = create_wallet_header(false, AeadFlag::GcmSiv, PepperFlag::None, 64, None).unwrap();
+        let enc = encrypt_wallet(&WalletSecretPayloadV3::new([6u8; 32]), password, &hdr).unwrap();
+        let bytes = bincode::options().with_limit(WALLET_MAX_SIZE).serialize(&WalletFile { header: hdr, enc }).unwrap();
+        fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn payment_uri_roundtrip_and_strict_parsing() {
+        let addr = test_addr(1);
+        let req = PaymentRequest { address: addr.clone(), amount: Some(1500), memo: Some("faktura 7/24 & zażółć=100%".into()) };
+        let uri = req.to_uri();
+        assert!(uri.starts_with(PAY_URI_SCHEME));
+        assert_eq!(PaymentRequest::parse(&uri).unwrap(), req);
+        let bare = PaymentRequest { address: addr.clone(), amount: None, memo: None };
+        assert_eq!(bare.to_uri(), format!("{}{}", PAY_URI_SCHEME, addr));
+        assert_eq!(PaymentRequest::parse(&bare.to_uri()).unwrap(), bare);
+
+        let parse = |q: &str| PaymentRequest::parse(&format!("{}{}?{}", PAY_URI_SCHEME, addr, q));
+        assert_eq!(parse("memo=a%20b").unwrap().memo.as_deref(), Some("a b"));
+        for bad in ["memo=%+1", "memo=%1", "memo=abc%", "memo=%G1", "memo=%-1", "memo=%FF", "amount=1&amount=2",
+                    "memo=a&memo=b", "amount=-1", "amount", "req-fee=1"] {
+            assert!(parse(bad).is_err(), "{bad}");
+        }
+        // nieznane parametry bez req- są ignorowane
+        assert_eq!(parse("label=shop&amount=3").unwrap().amount, Some(3));
+        assert!(PaymentRequest::parse(&format!("bitcoin:{}", addr)).is_err());
+        assert!(PaymentRequest::parse(&format!("{}tt1notanaddress", PAY_URI_SCHEME)).is_err());
+    }
+
+    #[test]
+    fn contacts_persist_in_wallet_file() {
+        let path = test_wallet("contacts", "contacts password");
+        let load = |pw: &str| {
+            let wf = load_wallet_file(&path)?;
+            decrypt_wallet(&wf.enc, pw, &wf.header)
+        };
+        add_contact(&path, "contacts password", "alice", &test_addr(2)).unwrap();
+        add_contact(&path, "contacts password", "bob", &test_addr(3)).unwrap();
+        assert!(add_contact(&path, "contacts password", "alice", &test_addr(4)).is_err());
+        assert!(add_contact(&path, "wrong password", "carol", &test_addr(4)).is_err());
+
+        let secret = load("contacts password").unwrap();
+        assert_eq!(secret.master32, [6u8; 32]);
+        let labels: Vec<&str> = secret.contacts.iter().map(|c| c.label.as_str()).collect();
+        assert_eq!(labels, ["alice", "bob"]);
+        assert_eq!(secret.contacts[1].address, test_addr(3));
+
+        remove_contact(&path, "contacts password", "alice").unwrap();
+        assert!(remove_contact(&path, "contacts password", "alice").is_err());
+        let secret = load("contacts password").unwrap();
+        assert_eq!(secret.contacts.len(), 1);
+        assert_eq!(secret.contacts[0].label, "bob");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn view_key_scans_like_the_wallet_and_cannot_spend() {
+        use pot80_zk_host::keysearch::{AadMode, KeySearchCtx, ValueConceal};
+        let master = [8u8; 32];
+        let ks = Keyset::derive(&master, 1, 2);
+        let vk = ViewKeyFile::from_keyset(&ks).unwrap();
+        let txt = serde_json::to_string_pretty(&vk).unwrap();
+        assert!(!txt.contains(&hex::encode(ks.spend_sk.to_bytes())));
+        assert!(!txt.contains(&hex::encode(master)));
+        assert!(!txt.contains("spend_sk"));
+
+        let path = std::env::temp_dir().join(format!("tt-test-view-{}.json", std::process::id()));
+        fs::write(&path, &txt).unwrap();
+        let view = keysearch_ctxs(load_scan_secrets(None, Some(path.clone()), 0, 1).unwrap(), 0).unwrap();
+        fs::remove_file(&path).unwrap();
+        let wallet = keysearch_ctxs(wallet_scan_secrets(&master, 1, 3).unwrap(), 0).unwrap();
+
+        let other = Keyset::derive(&[9u8; 32], 1, 2);
+        let pairs: Vec<([u8; 32], Vec<u8>)> = (0..6u8)
+            .map(|i| {
+                let to = if i % 2 == 0 { &ks } else { &other };
+                let c_out = [i; 32];
+                (c_out, KeySearchCtx::build_enc_hint_ext(&to.scan_pk, &c_out, AadMode::COutOnly, None, ValueConceal::Plain(u64::from(i)), &[]))
+            })
+            .collect();
+        let scan = |ctxs: &[(String, KeySearchCtx)]| -> Vec<(usize, [u8; 32], Option<HintPayload>)> {
+            pairs.iter().enumerate()
+                .filter_map(|(n, (c, e))| match match_pair(ctxs, c, e, None) {
+                    PairMatch::Hit(h) => Some((n, h.k_search, h.payload)),
+                    _ => None,
+                })
+                .collect()
+        };
+        let from_view = scan(&view);
+        assert_eq!(from_view.iter().map(|h| h.0).collect::<Vec<_>>(), vec![0, 2, 4]);
+        assert_eq!(from_view, scan(&wallet));
+    }
+
+    #[test]
+    fn net_id_mismatch_is_not_confused_with_missing_payload() {
+        use pot80_zk_host::keysearch::{AadMode, KeySearchCtx, ValueConceal};
+        let master = [4u8; 32];
+        let ks = Keyset::derive(&master, 0, 0);
+        let ctxs = keysearch_ctxs(wallet_scan_secrets(&master, 0, 1).unwrap(), 0).unwrap();
+        let c_out = [1u8; 32];
+        let hint = |m: AadMode| KeySearchCtx::build_enc_hint_ext(&ks.scan_pk, &c_out, m, None, ValueConceal::Plain(7), &[]);
+
+        let unbound = hint(AadMode::COutOnly);
+        assert_eq!(match_pair(&ctxs, &c_out, &unbound, Some(7)), PairMatch::WrongNet);
+        assert!(matches!(match_pair(&ctxs, &c_out, &unbound, None), PairMatch::Hit(PairHit { payload: Some(_), .. })));
+
+        let bound = hint(AadMode::NetIdAndCOut(7));
+        assert!(matches!(match_pair(&ctxs, &c_out, &bound, Some(7)), PairMatch::Hit(PairHit { payload: Some(_), .. })));
+
+        let other_net = hint(AadMode::NetIdAndCOut(8));
+        assert_eq!(match_pair(&ctxs, &c_out, &other_net, Some(7)), PairMatch::WrongNet);
+
+        // sam tag bez payloadu to trafienie, nie odrzucenie
+        let tag_only = &bound[..TAG_ONLY_HINT_BYTES];
+        assert!(matches!(match_pair(&ctxs, &c_out, tag_only, Some(7)), PairMatch::Hit(PairHit { payload: None, .. })));
+        assert_eq!(match_pair(&ctxs, &[2u8; 32], &bound, Some(7)), PairMatch::Miss);
+    }
+
+    #[test]
+    fn backup_roundtrip_checks_integrity_and_migrates() {
+        let kdf = KdfHeader { kind: KdfKind::Kmac256V1 { salt32: [3u8; 32] }, info: "test".into() };
+        let mut payload = WalletSecretPayloadV3::new([9u8; 32]);
+        payload.contacts.push(Contact { label: "alice".into(), address: "tt1q".into() });
+        let pt = bincode::options().with_limit(WALLET_MAX_SIZE).serialize(&payload).unwrap();
+
+        let b = seal_backup(&pt, WALLET_VERSION, [1u8; 16], "backup password", kdf.clone()).unwrap();
+        let back = open_backup(&b, "backup password").unwrap();
+        assert_eq!(back.master32, [9u8; 32]);
+        assert_eq!(back.contacts.len(), 1);
+        assert!(open_backup(&b, "wrong password!").is_err());
+
+        let mut bad = b.clone();
+        bad.enc[0] ^= 1;
+        assert!(check_backup(&bad).is_err());
+        let mut bad = b;
+        bad.header.wallet_version = WALLET_VERSION + 1;
+        assert!(check_backup(&bad).is_err());
+
+        // kopia z payloadem v4 przechodzi przez łańcuch migracji
+        let old = seal_backup(&v4_payload([5u8; 32]), 4, [2u8; 16], "backup password", kdf).unwrap();
+        let migrated = open_backup(&old, "backup password").unwrap();
+        assert_eq!(migrated.master32, [5u8; 32]);
+        assert!(migrated.contacts.is_empty());
+    }
+}
@@ -13,13 +13,24 @@ pub type Q = u64;                // Q32.32
 pub const ONE_Q: Q = 1u64 << 32; // 1.0
 
 #[inline]
-fn qmul(a: Q, b: Q) -> Q {
+pub(crate) fn qmul(a: Q, b: Q) -> Q {
     let z = (a as u128) * (b as u128);
     let shifted = z >> 32;
     // Clamp to u64::MAX to prevent overflow
     shifted.min(u64::MAX as u128) as u64
 }
 
+/// `base^exp` by squaring (at most 64 rounds)
+pub(crate) fn qpow(mut base: Q, mut exp: u64) -> Q {
+    let mut acc = ONE_Q;
+    while exp > 0 {
+        if exp & 1 == 1 { acc = qmul(acc, base); }
+        exp >>= 1;
+        if exp > 0 { base = qmul(base, base); }
+    }
+    acc
+}
+
 #[inline]
 fn qadd(a: Q, b: Q) -> Q { a.saturating_add(b) }
 
@@ -31,6 +42,26 @@ fn qdiv(a: Q, b: Q) -> Q {
     }
 }
 
+/// `a * b`, or None when the product does not fit in Q32.32
+#[inline]
+pub fn checked_qmul(a: Q, b: Q) -> Option<Q> {
+    u64::try_from((u128::from(a) * u128::from(b)) >> 32).ok()
+}
+
+/// `a / b`, or None for `b == 0` or a quotient that does not fit in Q32.32
+#[inline]
+pub fn checked_qdiv(a: Q, b: Q) -> Option<Q> {
+    if b == 0 { return None; }
+    u64::try_from((u128::from(a) << 32) / u128::from(b)).ok()
+}
+
+/// `num / den` as Q32.32, or None for `den == 0` or overflow
+#[inline]
+pub fn checked_q_from_ratio128(num: u128, den: u128) -> Option<Q> {
+    if den == 0 { return None; }
+    u64::try_from(u128::from(ONE_Q).checked_mul(num)? / den).ok()
+}
+
 #[inline]
 pub fn q_from_ratio(num: u64, den: u64) -> Q {
     ((u128::from(ONE_Q) * u128::from(num)) / u128::from(den.max(1))) as u64
@@ -39,8 +70,7 @@ pub fn q_from_ratio(num: u64, den: u64) -> Q {
 #[inline]
 pub fn q_from_ratio128(num: u128, den: u128) -> Q {
     if den == 0 { return 0; }
-    let z = (u128::from(ONE_Q) * num) / den;
-    z.min(u128::from(u64::MAX)) as u64
+    checked_q_from_ratio128(num, den).unwrap_or(u64::MAX)
 }
 
 #[inline]
@@ -197,6 +227,12 @@ impl EpochSnapshot {
         *self.trust_q_at_snapshot.get(who).unwrap_or(&0) 
     }
 
+    /// Waga głosu/sortition: stake_q * trust_q (0 gdy brak w snapshotcie)
+    #[inline]
+    pub fn weight_q_of(&self, who: &NodeId) -> Q {
+        qmul(self.stake_q_of(who), self.trust_q_of(who))
+    }
+
     /// Zwraca indeks liścia w `order`
     pub fn leaf_index_of(&self, who: &NodeId) -> Option<u64> {
         self.order.iter().position(|w| w == who).map(|i| i as u64)
@@ -219,7 +255,7 @@ impl EpochSnapshot {
 pub struct MerkleProof { pub leaf_index: u64, pub siblings: Vec<[u8; 32]> }
 
 #[inline]
-fn merkle_leaf_hash(who: &NodeId, stake_q: StakeQ, trust_q: Q) -> [u8; 32] {
+pub(crate) fn merkle_leaf_hash(who: &NodeId, stake_q: StakeQ, trust_q: Q) -> [u8; 32] {
     kmac256_hash(b"WGT.v1", &[
         who,
         &stake_q.to_le_bytes(),
@@ -232,7 +268,7 @@ fn merkle_parent(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
     kmac256_hash(b"MRK.v1", &[a, b])
 }
 
-fn merkle_root(leaves: &[[u8; 32]]) -> [u8; 32] {
+pub(crate) fn merkle_root(leaves: &[[u8; 32]]) -> [u8; 32] {
     if leaves.is_empty() {
         // Special hash for empty tree to avoid collision with [0u8; 32]
         return kmac256_hash(b"MRK.empty.v1", &[]);
@@ -252,7 +288,7 @@ fn merkle_root(leaves: &[[u8; 32]]) -> [u8; 32] {
     layer[0]
 }
 
-fn merkle_build_proof(leaves: &[[u8; 32]], leaf_idx: u64) -> MerkleProof {
+pub(crate) fn merkle_build_proof(leaves: &[[u8; 32]], leaf_idx: u64) -> MerkleProof {
     let mut idx = leaf_idx as usize;
     let mut layer = leaves.to_vec();
     let mut siblings = Vec::<[u8; 32]>::new();
@@ -308,6 +344,8 @@ pub struct RandaoBeacon {
     pub epochs: HashMap<u64, RandaoEpoch>,
     pub prev_beacon: [u8; 32],
     pub slash_noreveal_bps: u32,
+    /// 0 = commit v1 (bez separacji sieci), inaczej commit v2 wiąże network_id
+    pub network_id: u32,
 }
 
 impl RandaoBeacon {
@@ -315,9 +353,16 @@ impl RandaoBeacon {
         Self { 
             epochs: HashMap::new(), 
             prev_beacon: genesis_beacon, 
-            slash_noreveal_bps 
+            slash_noreveal_bps,
+            network_id: 0,
         }
     }
+
+    /// Bind commits to a network so they cannot be replayed on another one
+    pub fn with_network_id(mut self, network_id: u32) -> Self {
+        self.network_id = network_id;
+        self
+    }
     
     #[inline]
     pub fn commit_hash(epoch: u64, who: &NodeId, r: &[u8; 32]) -> [u8; 32] {
@@ -327,6 +372,18 @@ impl RandaoBeacon {
             r,
         ])
     }
+
+    /// Commit hash for `network_id` (0 = legacy v1 hash)
+    #[inline]
+    pub fn commit_hash_net(network_id: u32, epoch: u64, who: &NodeId, r: &[u8; 32]) -> [u8; 32] {
+        if network_id == 0 { return Self::commit_hash(epoch, who, r); }
+        kmac256_hash(b"RANDAO.commit.v2", &[
+            &network_id.to_le_bytes(),
+            &epoch.to_le_bytes(),
+            who,
+            r,
+        ])
+    }
     
     pub fn commit(&mut self, epoch: u64, who: NodeId, c: [u8; 32]) {
         self.epochs.entry(epoch).or_default().commits.insert(who, c);
@@ -337,7 +394,7 @@ impl RandaoBeacon {
         match e.commits.get(&who) {
             None => false,
             Some(&c) => {
-                if Self::commit_hash(epoch, &who, &r) != c { return false; }
+                if Self::commit_hash_net(self.network_id, epoch, &who, &r) != c { return false; }
                 e.reveals.insert(who, r); 
                 true
             }
@@ -378,8 +435,9 @@ impl RandaoBeacon {
     pub fn value(&self, epoch: u64, slot: u64) -> [u8; 32] {
         // stabilny seed dla danej epoki
         let base = match self.epochs.get(&epoch) {
-            Some(e) if e.finalized && e.seed != [0u8; 32] => e.seed,
-            Some(e) if !e.finalized => e.seed, // Use seed even if not finalized
+            Some(e) if e.finalized => e.seed,
+            // niedomknięta epoka: finalize_epoch zapisze prev_beacon jako jej seed,
+            // więc wartość slotu nie zmienia się po domknięciu
             _ => self.prev_beacon,
         };
         kmac256_hash(b"RANDAO.slot.v1", &[
@@ -426,12 +484,23 @@ fn bound_u64(p_q: Q) -> u64 {
     (((p_q as u128) << 32).min(u128::from(u64::MAX))) as u64
 }
 
-#[inline]
-fn prob_threshold_q(lambda_q: Q, stake_q: Q, trust_q: Q, sum_weights_q: Q) -> Q {
+/// Sortition threshold with a flag telling whether the result was distorted:
+/// `sum_weights_q` raised to its floor, an intermediate product/quotient
+/// saturated, or the probability clamped to 1.
+pub fn prob_threshold_checked(lambda_q: Q, stake_q: Q, trust_q: Q, sum_weights_q: Q) -> (Q, bool) {
     // Ensure minimum sum_weights_q to avoid division issues
-    let sum = sum_weights_q.max(ONE_Q / 1_000_000); // Minimum 0.000001
+    let floor = ONE_Q / 1_000_000; // Minimum 0.000001
+    let sum = sum_weights_q.max(floor);
     let wi = qmul(stake_q, qclamp01(trust_q));
-    qclamp01(qmul(lambda_q, qdiv(wi, sum)))
+    let p = qmul(lambda_q, qdiv(wi, sum));
+    let exact = checked_qdiv(wi, sum).and_then(|sh| checked_qmul(lambda_q, sh)).is_some();
+    let saturated = sum_weights_q < floor || !exact || p > ONE_Q;
+    (qclamp01(p), saturated)
+}
+
+#[inline]
+fn prob_threshold_q(lambda_q: Q, stake_q: Q, trust_q: Q, sum_weights_q: Q) -> Q {
+    prob_threshold_checked(lambda_q, stake_q, trust_q, sum_weights_q).0
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -521,7 +590,7 @@ pub fn verify_leader_with_witness(
 
 /* ===== Equivocation ===== */
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Proposal { pub who: NodeId, pub slot: u64, pub header_hash: [u8; 32] }
 
 pub fn detect_equivocation(proposals: &[Proposal]) -> bool {
@@ -701,6 +770,19 @@ mod tests {
         assert_eq!(v_pre, v_post, "sortition seed must be stable across finalize");
     }
 
+    #[test]
+    fn randao_value_unchanged_by_finalize_with_reveals() {
+        let mut b = RandaoBeacon::new(0, [7u8; 32]);
+        let (e, who, r) = (3u64, [8u8; 32], [9u8; 32]);
+        b.commit(e, who, RandaoBeacon::commit_hash(e, &who, &r));
+        assert!(b.reveal(e, who, r));
+        // epoka ma już wpis, ale nie jest domknięta
+        let pre = b.value(e, 5);
+        let _ = b.finalize_epoch(e);
+        assert_eq!(b.value(e, 5), pre);
+        assert_ne!(b.value(e + 1, 5), pre);
+    }
+
     #[test]
     fn empty_merkle_root() {
         let root = super::merkle_root(&[]);
@@ -742,4 +824,96 @@ mod tests {
         ];
         assert!(!super::detect_equivocation(&proposals3));
     }
+
+    #[test]
+    fn checked_q_arithmetic_reports_overflow() {
+        assert_eq!(checked_qmul(ONE_Q, ONE_Q), Some(ONE_Q));
+        assert_eq!(checked_qmul(u64::MAX, 2 * ONE_Q), None);
+        assert_eq!(checked_qdiv(ONE_Q, 0), None);
+        assert_eq!(checked_qdiv(u64::MAX, ONE_Q / 2), None);
+        assert_eq!(checked_q_from_ratio128(1, 0), None);
+        assert_eq!(checked_q_from_ratio128(1u128 << 32, 1), None);
+        // q_from_ratio keeps truncating; overflow is detected via the checked variant
+        assert_eq!(q_from_ratio(u64::MAX, 1), u64::MAX << 32);
+        assert_eq!(checked_q_from_ratio128(u64::MAX.into(), 1), None);
+
+        let (p, sat) = prob_threshold_checked(ONE_Q, ONE_Q / 4, ONE_Q, ONE_Q);
+        assert_eq!((p, sat), (ONE_Q / 4, false));
+        assert_eq!(prob_threshold_checked(4 * ONE_Q, ONE_Q / 2, ONE_Q, ONE_Q), (ONE_Q, true));
+        assert!(prob_threshold_checked(ONE_Q, 1, ONE_Q, 0).1);
+    }
+
+    mod props {
+        use super::super::*;
+        use proptest::prelude::*;
+
+        proptest! {
+            #[test]
+            fn checked_qmul_agrees_with_saturating(a: u64, b: u64) {
+                match checked_qmul(a, b) {
+                    Some(z) => prop_assert_eq!(z, qmul(a, b)),
+                    None => prop_assert_eq!(qmul(a, b), u64::MAX),
+                }
+            }
+
+            #[test]
+            fn checked_qdiv_agrees_with_saturating(a: u64, b in 1u64..) {
+                match checked_qdiv(a, b) {
+                    Some(z) => prop_assert_eq!(z, qdiv(a, b)),
+                    None => prop_assert_eq!(qdiv(a, b), u64::MAX),
+                }
+            }
+
+            #[test]
+            fn qmul_identity_and_monotone(a: u64, b: u64, c: u64) {
+                prop_assert_eq!(qmul(a, ONE_Q), a);
+                let (lo, hi) = if a <= b { (a, b) } else { (b, a) };
+                prop_assert!(qmul(lo, c) <= qmul(hi, c));
+            }
+
+            #[test]
+            fn qmul_associative_within_tolerance(a in 0..=4 * ONE_Q, b in 0..=4 * ONE_Q, c in 0..=4 * ONE_Q) {
+                // każde mnożenie obcina < 1 ulp, przemnożone przez czynnik <= 4
+                let l = qmul(qmul(a, b), c);
+                let r = qmul(a, qmul(b, c));
+                prop_assert!(l.abs_diff(r) <= 5, "{} vs {}", l, r);
+            }
+
+            #[test]
+            fn qdiv_roundtrip_within_tolerance(a in 0..=ONE_Q, b in ONE_Q / 1024..=ONE_Q) {
+                let back = qmul(qdiv(a, b), b);
+                prop_assert!(back <= a && a - back <= 2);
+            }
+
+            #[test]
+            fn q_from_ratio_matches_checked_in_range(n1: u64, n2: u64, den in 1u64..) {
+                let q1 = q_from_ratio(n1, den);
+                match checked_q_from_ratio128(n1.into(), den.into()) {
+                    Some(z) => prop_assert_eq!(q1, z),
+                    None => prop_assert_eq!(q1, ((u128::from(n1) << 32) / u128::from(den)) as u64),
+                }
+                if n1 <= n2 && checked_q_from_ratio128(n2.into(), den.into()).is_some() {
+                    prop_assert!(q1 <= q_from_ratio(n2, den));
+                }
+            }
+
+            #[test]
+            fn prob_threshold_bounded_and_monotone(
+                lambda in 0..=4 * ONE_Q,
+                s1 in 0..=ONE_Q,
+                s2 in 0..=ONE_Q,
+                trust in 0..=2 * ONE_Q,
+                sum: u64,
+            ) {
+                let (p1, sat1) = prob_threshold_checked(lambda, s1, trust, sum);
+                let (p2, _) = prob_threshold_checked(lambda, s2, trust, sum);
+                prop_assert!(p1 <= ONE_Q && p2 <= ONE_Q);
+                if s1 <= s2 { prop_assert!(p1 <= p2); } else { prop_assert!(p2 <= p1); }
+                if !sat1 {
+                    let share = checked_qdiv(qmul(s1, trust.min(ONE_Q)), sum).unwrap();
+                    prop_assert_eq!(Some(p1), checked_qmul(lambda, share));
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,180 @@
+//! Validator-side RANDAO duties
+//! At the start of each epoch a fresh secret is committed; it is revealed once
+//! the reveal window (`reveal_offset` slots into the epoch) opens. The pending
+//! secret must be persisted before broadcasting, so a restart mid-epoch can
+//! still reveal instead of being slashed for no-reveal.
+
+use crate::pot::{NodeId, RandaoBeacon};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RandaoAction {
+    Commit { epoch: u64, commit: [u8; 32] },
+    Reveal { epoch: u64, secret: [u8; 32] },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PendingSecret {
+    pub epoch: u64,
+    pub secret: [u8; 32],
+    pub revealed: bool,
+}
+
+pub const PENDING_SECRET_LEN: usize = 8 + 32 + 1;
+
+impl PendingSecret {
+    pub fn to_bytes(&self) -> [u8; PENDING_SECRET_LEN] {
+        let mut out = [0u8; PENDING_SECRET_LEN];
+        out[..8].copy_from_slice(&self.epoch.to_le_bytes());
+        out[8..40].copy_from_slice(&self.secret);
+        out[40] = u8::from(self.revealed);
+        out
+    }
+
+    pub fn from_bytes(b: &[u8]) -> Option<Self> {
+        if b.len() != PENDING_SECRET_LEN || b[40] > 1 { return None; }
+        let mut secret = [0u8; 32];
+        secret.copy_from_slice(&b[8..40]);
+        Some(Self { epoch: u64::from_le_bytes(b[..8].try_into().ok()?), secret, revealed: b[40] == 1 })
+    }
+}
+
+pub struct RandaoSchedule {
+    pub who: NodeId,
+    pub slots_per_epoch: u64,
+    /// First slot (within the epoch) of the reveal window; commits only before it
+    pub reveal_offset: u64,
+    /// Must match `RandaoBeacon::network_id` of the chain
+    pub network_id: u32,
+    pending: Option<PendingSecret>,
+}
+
+impl RandaoSchedule {
+    /// `network_id` is required so a restart cannot silently fall back to
+    /// the legacy (network 0) commit hash
+    pub fn new(network_id: u32, who: NodeId, slots_per_epoch: u64, reveal_offset: u64) -> Result<Self, &'static str> {
+        if slots_per_epoch == 0 { return Err("slots_per_epoch must be > 0"); }
+        if reveal_offset == 0 || reveal_offset >= slots_per_epoch {
+            return Err("reveal_offset must be inside the epoch and after slot 0");
+        }
+        Ok(Self { who, slots_per_epoch, reveal_offset, network_id, pending: None })
+    }
+
+    /// Restart with the secret persisted before the crash
+    pub fn resume(
+        network_id: u32,
+        who: NodeId,
+        slots_per_epoch: u64,
+        reveal_offset: u64,
+        pending: Option<PendingSecret>,
+    ) -> Result<Self, &'static str> {
+        let mut s = Self::new(network_id, who, slots_per_epoch, reveal_offset)?;
+        s.pending = pending;
+        Ok(s)
+    }
+
+    /// State to persist whenever `on_slot` returned an action
+    #[inline]
+    pub fn pending(&self) -> Option<&PendingSecret> { self.pending.as_ref() }
+
+    /// Duties for `slot`. `fresh_secret` is called only when a new commit is due.
+    pub fn on_slot(&mut self, slot: u64, fresh_secret: impl FnOnce() -> [u8; 32]) -> Option<RandaoAction> {
+        let epoch = slot / self.slots_per_epoch;
+        let offset = slot % self.slots_per_epoch;
+
+        match self.pending.as_mut() {
+            Some(p) if p.epoch == epoch => {
+                if p.revealed || offset < self.reveal_offset { return None; }
+                p.revealed = true;
+                Some(RandaoAction::Reveal { epoch, secret: p.secret })
+            }
+            // stara epoka (niezrevealowana = okno przepadło) albo brak sekretu
+            _ => {
+                // za późno na commit w tej epoce – czekamy na następną
+                if offset >= self.reveal_offset { return None; }
+                let secret = fresh_secret();
+                self.pending = Some(PendingSecret { epoch, secret, revealed: false });
+                let commit = RandaoBeacon::commit_hash_net(self.network_id, epoch, &self.who, &secret);
+                Some(RandaoAction::Commit { epoch, commit })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nid(n: u8) -> NodeId {
+        let mut id = [0u8; 32];
+        id[0] = n;
+        id
+    }
+
+    #[test]
+    fn commit_then_reveal_feeds_beacon() {
+        let who = nid(1);
+        let mut sch = RandaoSchedule::new(0, who, 10, 5).unwrap();
+        let mut beacon = RandaoBeacon::new(0, [0u8; 32]);
+        let mut acts = Vec::new();
+        for slot in 0..20 {
+            if let Some(a) = sch.on_slot(slot, || [slot as u8 + 1; 32]) { acts.push((slot, a)); }
+        }
+        assert_eq!(acts.iter().map(|(s, _)| *s).collect::<Vec<_>>(), vec![0, 5, 10, 15]);
+        for (_, a) in acts {
+            match a {
+                RandaoAction::Commit { epoch, commit } => beacon.commit(epoch, who, commit),
+                RandaoAction::Reveal { epoch, secret } => assert!(beacon.reveal(epoch, who, secret)),
+            }
+        }
+    }
+
+    #[test]
+    fn restart_mid_epoch_still_reveals() {
+        let who = nid(1);
+        let mut sch = RandaoSchedule::new(0, who, 10, 5).unwrap();
+        assert!(matches!(sch.on_slot(12, || [9u8; 32]), Some(RandaoAction::Commit { epoch: 1, .. })));
+        let saved = sch.pending().unwrap().to_bytes();
+
+        let restored = PendingSecret::from_bytes(&saved);
+        let mut sch = RandaoSchedule::resume(0, who, 10, 5, restored).unwrap();
+        assert!(sch.on_slot(13, || unreachable!()).is_none());
+        assert_eq!(sch.on_slot(17, || unreachable!()), Some(RandaoAction::Reveal { epoch: 1, secret: [9u8; 32] }));
+        assert!(sch.on_slot(18, || unreachable!()).is_none());
+        // start w oknie reveal: brak commitu do następnej epoki
+        let mut late = RandaoSchedule::new(0, who, 10, 5).unwrap();
+        assert!(late.on_slot(27, || [1u8; 32]).is_none());
+        assert!(PendingSecret::from_bytes(&[0u8; 3]).is_none());
+    }
+
+    #[test]
+    fn commits_are_bound_to_network() {
+        let who = nid(1);
+        let mut sch = RandaoSchedule::new(7, who, 10, 5).unwrap();
+        let Some(RandaoAction::Commit { commit, .. }) = sch.on_slot(0, || [4u8; 32]) else { panic!("no commit") };
+
+        let mut other = RandaoBeacon::new(0, [0u8; 32]).with_network_id(8);
+        other.commit(0, who, commit);
+        assert!(!other.reveal(0, who, [4u8; 32]));
+        let mut ours = RandaoBeacon::new(0, [0u8; 32]).with_network_id(7);
+        ours.commit(0, who, commit);
+        assert!(ours.reveal(0, who, [4u8; 32]));
+    }
+
+    #[test]
+    fn restart_keeps_network_binding() {
+        let who = nid(1);
+        let mut beacon = RandaoBeacon::new(0, [0u8; 32]).with_network_id(7);
+        let mut sch = RandaoSchedule::new(7, who, 10, 5).unwrap();
+        let Some(RandaoAction::Commit { epoch, commit }) = sch.on_slot(10, || [6u8; 32]) else { panic!("no commit") };
+        beacon.commit(epoch, who, commit);
+        let saved = sch.pending().unwrap().to_bytes();
+
+        let mut sch = RandaoSchedule::resume(7, who, 10, 5, PendingSecret::from_bytes(&saved)).unwrap();
+        assert_eq!(sch.network_id, 7);
+        let Some(RandaoAction::Reveal { epoch, secret }) = sch.on_slot(15, || unreachable!()) else { panic!("no reveal") };
+        assert!(beacon.reveal(epoch, who, secret));
+        // następna epoka po restarcie też commituje z network_id
+        let Some(RandaoAction::Commit { commit, .. }) = sch.on_slot(20, || [7u8; 32]) else { panic!("no commit") };
+        assert_eq!(commit, RandaoBeacon::commit_hash_net(7, 2, &who, &[7u8; 32]));
+    }
+}
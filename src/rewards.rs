@@ -0,0 +1,182 @@
+//! Block and RANDAO participation rewards
+//! Per-block issuance follows `InflationSchedule`; the proposer gets issuance
+//! plus fees, every timely RANDAO revealer gets `reveal_reward_bps` of the
+//! per-block issuance when the epoch is finalized.
+
+use std::collections::BTreeMap;
+
+use crate::delegation::{AccountId, Delegations};
+use crate::pot::{q_from_basis_points, qpow, NodeId, RandaoBeacon, Registry};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InflationSchedule {
+    pub initial_per_block: u64,
+    /// Issuance drops by `decay_bps` every `decay_every_epochs` (0 = constant)
+    pub decay_bps: u32,
+    pub decay_every_epochs: u64,
+    /// Issuance never drops below this
+    pub floor_per_block: u64,
+    pub reveal_reward_bps: u32,
+}
+
+impl InflationSchedule {
+    pub fn new(
+        initial_per_block: u64,
+        decay_bps: u32,
+        decay_every_epochs: u64,
+        floor_per_block: u64,
+        reveal_reward_bps: u32,
+    ) -> Result<Self, &'static str> {
+        if decay_bps > 10_000 || reveal_reward_bps > 10_000 { return Err("bps must be <= 10000"); }
+        if floor_per_block > initial_per_block { return Err("floor above initial issuance"); }
+        Ok(Self { initial_per_block, decay_bps, decay_every_epochs, floor_per_block, reveal_reward_bps })
+    }
+
+    /// `initial * (1 - decay)^steps` in Q32.32, rounded to the nearest unit
+    pub fn block_issuance(&self, epoch: u64) -> u64 {
+        if self.decay_bps == 0 || self.decay_every_epochs == 0 { return self.initial_per_block; }
+        let keep_q = q_from_basis_points(10_000 - self.decay_bps);
+        let f = qpow(keep_q, epoch / self.decay_every_epochs);
+        let r = (u128::from(self.initial_per_block) * u128::from(f) + (1u128 << 31)) >> 32;
+        (r as u64).clamp(self.floor_per_block, self.initial_per_block)
+    }
+
+    #[inline]
+    pub fn reveal_reward(&self, epoch: u64) -> u64 {
+        ((u128::from(self.block_issuance(epoch)) * u128::from(self.reveal_reward_bps)) / 10_000) as u64
+    }
+}
+
+/// Validators that revealed in `epoch`, in deterministic order
+pub fn revealers(beacon: &RandaoBeacon, epoch: u64) -> Vec<NodeId> {
+    let mut v: Vec<NodeId> = beacon.epochs.get(&epoch)
+        .map(|e| e.reveals.keys().copied().collect())
+        .unwrap_or_default();
+    v.sort();
+    v
+}
+
+/// Inputs for sharing a block reward with delegators
+pub struct DelegationSplit<'a> {
+    pub reg: &'a Registry,
+    pub delegations: &'a Delegations,
+    pub commission_bps: u32,
+}
+
+#[derive(Default)]
+pub struct RewardLedger {
+    balances: BTreeMap<AccountId, u64>,
+    pub total_issued: u64,
+    pub total_fees: u64,
+}
+
+impl RewardLedger {
+    #[inline]
+    pub fn balance(&self, who: &AccountId) -> u64 {
+        self.balances.get(who).copied().unwrap_or(0)
+    }
+
+    /// Drain an account's accrued rewards (e.g. into the state's balances)
+    pub fn take(&mut self, who: &AccountId) -> u64 {
+        self.balances.remove(who).unwrap_or(0)
+    }
+
+    fn credit(&mut self, who: AccountId, amount: u64) {
+        if amount == 0 { return; }
+        let b = self.balances.entry(who).or_insert(0);
+        *b = b.saturating_add(amount);
+    }
+
+    /// Credit the proposer of a block in `epoch`; returns the total credited
+    pub fn credit_block(&mut self, schedule: &InflationSchedule, epoch: u64, proposer: &NodeId, fees: u64) -> u64 {
+        let issued = schedule.block_issuance(epoch);
+        self.total_issued = self.total_issued.saturating_add(issued);
+        self.total_fees = self.total_fees.saturating_add(fees);
+        let amount = issued.saturating_add(fees);
+        self.credit(*proposer, amount);
+        amount
+    }
+
+    /// Like `credit_block`, but shares the amount with the proposer's delegators
+    pub fn credit_block_delegated(
+        &mut self,
+        schedule: &InflationSchedule,
+        epoch: u64,
+        proposer: &NodeId,
+        fees: u64,
+        split: &DelegationSplit<'_>,
+    ) -> u64 {
+        let issued = schedule.block_issuance(epoch);
+        self.total_issued = self.total_issued.saturating_add(issued);
+        self.total_fees = self.total_fees.saturating_add(fees);
+        let amount = issued.saturating_add(fees);
+        let (own, shares) = split.delegations.split_reward(split.reg, proposer, amount, split.commission_bps);
+        self.credit(*proposer, own);
+        for (who, s) in shares { self.credit(who, s); }
+        amount
+    }
+
+    /// Reward every revealer of a finalized epoch; returns the total issued
+    pub fn credit_reveals(&mut self, schedule: &InflationSchedule, epoch: u64, revealers: &[NodeId]) -> u64 {
+        let r = schedule.reveal_reward(epoch);
+        for who in revealers { self.credit(*who, r); }
+        let total = r.saturating_mul(revealers.len() as u64);
+        self.total_issued = self.total_issued.saturating_add(total);
+        total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nid(n: u8) -> NodeId {
+        let mut id = [0u8; 32];
+        id[0] = n;
+        id
+    }
+
+    #[test]
+    fn schedule_decays_to_floor() {
+        let s = InflationSchedule::new(1000, 1000, 10, 500, 500).unwrap();
+        assert_eq!(s.block_issuance(0), 1000);
+        assert_eq!(s.block_issuance(9), 1000);
+        assert_eq!(s.block_issuance(10), 900);
+        assert_eq!(s.block_issuance(20), 810);
+        assert_eq!(s.block_issuance(u64::MAX), 500);
+        assert_eq!(s.reveal_reward(0), 50);
+        assert!(InflationSchedule::new(10, 0, 0, 11, 0).is_err());
+    }
+
+    #[test]
+    fn closed_form_tracks_exact_decay() {
+        let s = InflationSchedule::new(5_000_000, 250, 1, 0, 0).unwrap();
+        for epoch in 0..200u64 {
+            let exact = (5_000_000.0 * 0.975f64.powi(epoch as i32)).round() as u64;
+            assert!(s.block_issuance(epoch).abs_diff(exact) <= 1, "epoch {epoch}");
+        }
+        // długi ogon bez floor: stały koszt zamiast setek tysięcy kroków
+        let slow = InflationSchedule::new(u64::MAX, 1, 1, 0, 0).unwrap();
+        assert_eq!(slow.block_issuance(u64::MAX), 0);
+        assert_eq!(InflationSchedule::new(700, 10_000, 1, 7, 0).unwrap().block_issuance(1), 7);
+    }
+
+    #[test]
+    fn ledger_credits_proposer_and_revealers() {
+        let s = InflationSchedule::new(1000, 0, 0, 0, 1000).unwrap();
+        let (p, r1, r2) = (nid(1), nid(2), nid(3));
+        let mut beacon = RandaoBeacon::new(0, [0u8; 32]);
+        for (who, secret) in [(r2, [2u8; 32]), (r1, [1u8; 32])] {
+            beacon.commit(0, who, RandaoBeacon::commit_hash(0, &who, &secret));
+            assert!(beacon.reveal(0, who, secret));
+        }
+        let mut l = RewardLedger::default();
+        assert_eq!(l.credit_block(&s, 0, &p, 25), 1025);
+        assert_eq!(l.credit_reveals(&s, 0, &revealers(&beacon, 0)), 200);
+        assert_eq!(l.balance(&p), 1025);
+        assert_eq!(l.balance(&r1), 100);
+        assert_eq!(l.total_issued, 1200);
+        assert_eq!(l.take(&p), 1025);
+        assert_eq!(l.balance(&p), 0);
+    }
+}
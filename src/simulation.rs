@@ -0,0 +1,434 @@
+//! Deterministic multi-node consensus simulation
+//! N in-process nodes driven slot by slot (virtual time, no threads or I/O).
+//! Epoch state (registry, trust, RANDAO beacon, snapshots) is shared, as if
+//! every node applied the same epoch transitions; each node keeps its own view
+//! of the block tree (fork choice, orphans, equivocation tracker), which is
+//! what partitions and Byzantine proposers can disturb.
+//! Everything is derived from `SimConfig::seed`, so a failing run replays exactly.
+
+use crate::crypto_kmac_consensus::kmac256_hash;
+use crate::epoch::EpochManager;
+use crate::evidence::{ProposalTracker, SignedHeader};
+use crate::fork_choice::{BlockId, ForkChoice, OrphanPool};
+use crate::pot::{
+    verify_leader_with_witness, EpochSnapshot, NodeId, PotParams, Proposal, RandaoBeacon,
+    Registry, TrustParams, TrustState, ONE_Q,
+};
+use crate::randao_schedule::{RandaoAction, RandaoSchedule};
+use crate::snapshot::WeightWitnessV1;
+use std::collections::{BTreeSet, HashMap};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Behavior {
+    Honest,
+    /// Proposes two different blocks whenever eligible
+    Equivocate,
+    /// Commits to RANDAO but never reveals
+    WithholdReveal,
+    /// Proposes every slot with an inflated weight witness
+    InvalidProof,
+}
+
+/// Nodes `[0, split)` and `[split, n)` cannot reach each other in
+/// `[from_slot, until_slot)`; blocked messages are delivered at `until_slot`.
+#[derive(Clone, Copy, Debug)]
+pub struct Partition {
+    pub from_slot: u64,
+    pub until_slot: u64,
+    pub split: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct SimConfig {
+    pub seed: [u8; 32],
+    pub slots: u64,
+    pub slots_per_epoch: u64,
+    pub params: PotParams,
+    /// One entry per node; every node bonds `stake`
+    pub behaviors: Vec<Behavior>,
+    pub stake: u64,
+    pub partition: Option<Partition>,
+}
+
+impl SimConfig {
+    /// Equal stakes, full trust, about one leader per slot
+    pub fn new(seed: [u8; 32], slots: u64, behaviors: Vec<Behavior>) -> Self {
+        let trust = TrustParams { alpha_q: ONE_Q, beta_q: 0, init_q: ONE_Q / 2 };
+        Self {
+            seed,
+            slots,
+            slots_per_epoch: 8,
+            params: PotParams { trust, lambda_q: ONE_Q, min_bond: 1, slash_noreveal_bps: 1000 },
+            behaviors,
+            stake: 1000,
+            partition: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SimBlock {
+    pub id: BlockId,
+    pub parent: BlockId,
+    pub slot: u64,
+    pub epoch: u64,
+    pub proposer: NodeId,
+    pub witness: WeightWitnessV1,
+    /// Proposer signature over the header (`SignedHeader::signing_msg`)
+    pub sig: Vec<u8>,
+}
+
+impl SimBlock {
+    fn header(&self) -> SignedHeader {
+        SignedHeader {
+            network_id: 0,
+            proposal: Proposal { who: self.proposer, slot: self.slot, header_hash: self.id },
+            sig: self.sig.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SimReport {
+    pub heads: Vec<BlockId>,
+    pub head_heights: Vec<u64>,
+    pub proposed: usize,
+    /// Deliveries rejected by sortition/witness checks (summed over nodes)
+    pub rejected: usize,
+    pub equivocators: BTreeSet<NodeId>,
+    pub slashed_noreveal: BTreeSet<NodeId>,
+    /// Broken safety/liveness invariants; empty for a correct run
+    pub violations: Vec<String>,
+}
+
+/// Shared epoch-level state used to validate blocks
+struct Shared {
+    seed: [u8; 32],
+    params: PotParams,
+    reg: Registry,
+    trust: TrustState,
+    beacon: RandaoBeacon,
+    snapshots: HashMap<u64, EpochSnapshot>,
+}
+
+impl Shared {
+    /// Stand-in for Falcon: every node's signing key is derived from the seed
+    fn sign(&self, who: &NodeId, msg: &[u8; 32]) -> Vec<u8> {
+        kmac256_hash(b"SIM.sig.v1", &[&self.seed, who, msg]).to_vec()
+    }
+
+    fn verify_sig(&self, who: &NodeId, msg: &[u8; 32], sig: &[u8]) -> bool {
+        self.sign(who, msg) == sig
+    }
+
+    fn block_weight(&self, b: &SimBlock) -> Option<u128> {
+        let snap = self.snapshots.get(&b.epoch)?;
+        if b.witness.who != b.proposer { return None; }
+        // trust aktualizujemy tylko przy przejściu epoki, tu wystarczy kopia robocza
+        let mut scratch = TrustState::default();
+        verify_leader_with_witness(&self.reg, snap, &self.beacon, &mut scratch, &self.params, b.epoch, b.slot, &b.witness)
+    }
+}
+
+struct SimNode {
+    id: NodeId,
+    behavior: Behavior,
+    fc: ForkChoice,
+    orphans: OrphanPool<(SimBlock, u128)>,
+    tracker: ProposalTracker,
+    randao: RandaoSchedule,
+    proposer_of: HashMap<BlockId, NodeId>,
+    detected: BTreeSet<NodeId>,
+    rejected: usize,
+}
+
+impl SimNode {
+    fn receive(&mut self, b: SimBlock, shared: &Shared) {
+        if self.fc.contains(&b.id) || self.orphans.contains(&b.id) { return; }
+        let Some(weight) = shared.block_weight(&b) else {
+            self.rejected += 1;
+            return;
+        };
+        let h = b.header();
+        if !h.verify(&|w: &NodeId, m: &[u8; 32], s: &[u8]| shared.verify_sig(w, m, s)) {
+            self.rejected += 1;
+            return;
+        }
+        if let Some(ev) = self.tracker.observe(h) {
+            self.detected.insert(ev.offender());
+        }
+        if !self.fc.contains(&b.parent) {
+            // brakujący rodzic: w symulacji i tak dotrze (opóźnione wiadomości)
+            let _ = self.orphans.insert(b.id, b.parent, 1, (b, weight));
+            return;
+        }
+
+        let mut queue = vec![(b, weight)];
+        while let Some((blk, w)) = queue.pop() {
+            if self.fc.insert(blk.id, blk.parent, blk.slot, w).is_err() {
+                self.rejected += 1;
+                continue;
+            }
+            self.proposer_of.insert(blk.id, blk.proposer);
+            queue.extend(self.orphans.take_children(&blk.id).into_iter().map(|(_, v)| v));
+        }
+    }
+}
+
+pub struct Simulation {
+    cfg: SimConfig,
+    shared: Shared,
+    em: EpochManager,
+    nodes: Vec<SimNode>,
+    delayed: Vec<(usize, SimBlock)>,
+    equivocated: BTreeSet<NodeId>,
+    slashed: BTreeSet<NodeId>,
+    proposed: usize,
+    slot: u64,
+}
+
+impl Simulation {
+    pub fn new(cfg: SimConfig) -> Result<Self, &'static str> {
+        if cfg.behaviors.is_empty() { return Err("simulation needs at least one node"); }
+        if cfg.stake < cfg.params.min_bond { return Err("stake below min_bond"); }
+        if let Some(p) = cfg.partition {
+            if p.split == 0 || p.split >= cfg.behaviors.len() || p.until_slot <= p.from_slot {
+                return Err("invalid partition");
+            }
+        }
+        let em = EpochManager::new(cfg.slots_per_epoch)?;
+        let genesis = kmac256_hash(b"SIM.genesis.v1", &[&cfg.seed]);
+
+        let mut shared = Shared {
+            seed: cfg.seed,
+            params: cfg.params,
+            reg: Registry::default(),
+            trust: TrustState::default(),
+            beacon: RandaoBeacon::new(cfg.params.slash_noreveal_bps, kmac256_hash(b"SIM.beacon.v1", &[&cfg.seed])),
+            snapshots: HashMap::new(),
+        };
+        let mut nodes = Vec::with_capacity(cfg.behaviors.len());
+        for (i, behavior) in cfg.behaviors.iter().enumerate() {
+            let id = kmac256_hash(b"SIM.node.v1", &[&cfg.seed, &(i as u64).to_le_bytes()]);
+            shared.reg.insert(id, cfg.stake, true);
+            shared.trust.set(id, ONE_Q);
+            nodes.push(SimNode {
+                id,
+                behavior: *behavior,
+                fc: ForkChoice::new(genesis),
+                orphans: OrphanPool::new(1024, 1024),
+                tracker: ProposalTracker::new(cfg.slots.max(1)),
+                randao: RandaoSchedule::new(0, id, cfg.slots_per_epoch, cfg.slots_per_epoch / 2)?,
+                proposer_of: HashMap::new(),
+                detected: BTreeSet::new(),
+                rejected: 0,
+            });
+        }
+        Ok(Self {
+            cfg,
+            shared,
+            em,
+            nodes,
+            delayed: Vec::new(),
+            equivocated: BTreeSet::new(),
+            slashed: BTreeSet::new(),
+            proposed: 0,
+            slot: 0,
+        })
+    }
+
+    #[inline]
+    pub fn slot(&self) -> u64 { self.slot }
+
+    #[inline]
+    pub fn is_done(&self) -> bool { self.slot >= self.cfg.slots }
+
+    fn partitioned(&self, a: usize, b: usize) -> bool {
+        match self.cfg.partition {
+            Some(p) if (p.from_slot..p.until_slot).contains(&self.slot) => (a < p.split) != (b < p.split),
+            _ => false,
+        }
+    }
+
+    /// Advance one slot: epoch transition, RANDAO duties, proposals, delivery
+    pub fn step(&mut self) {
+        let slot = self.slot;
+        if self.cfg.partition.map(|p| p.until_slot == slot).unwrap_or(false) {
+            for (to, b) in std::mem::take(&mut self.delayed) {
+                self.nodes[to].receive(b, &self.shared);
+            }
+        }
+
+        let sh = &mut self.shared;
+        let tp = sh.params.trust;
+        for t in self.em.on_slot(slot, &mut sh.reg, &mut sh.trust, &mut sh.beacon, &tp, sh.params.min_bond) {
+            self.slashed.extend(t.slashed_noreveal.iter().copied());
+            sh.snapshots.insert(t.snapshot.epoch, t.snapshot);
+        }
+        let epoch = self.em.epoch_of(slot);
+
+        let seed = self.cfg.seed;
+        for n in &mut self.nodes {
+            let id = n.id;
+            let fresh = || kmac256_hash(b"SIM.secret.v1", &[&seed, &id, &epoch.to_le_bytes()]);
+            match n.randao.on_slot(slot, fresh) {
+                Some(RandaoAction::Commit { epoch, commit }) => sh.beacon.commit(epoch, id, commit),
+                Some(RandaoAction::Reveal { epoch, secret }) if n.behavior != Behavior::WithholdReveal => {
+                    sh.beacon.reveal(epoch, id, secret);
+                }
+                _ => {}
+            }
+        }
+
+        let mut outbox: Vec<(usize, SimBlock)> = Vec::new();
+        for (i, n) in self.nodes.iter().enumerate() {
+            let Some(snap) = sh.snapshots.get(&epoch) else { continue };
+            let Some(proof) = snap.build_proof(&n.id) else { continue };
+            let mut witness = WeightWitnessV1 {
+                who: n.id,
+                stake_q: snap.stake_q_of(&n.id),
+                trust_q: snap.trust_q_of(&n.id),
+                leaf_index: proof.leaf_index,
+                siblings: proof.siblings,
+            };
+            let parent = n.fc.head();
+            let make = |variant: u8, witness: WeightWitnessV1| {
+                let mut b = SimBlock {
+                    id: kmac256_hash(b"SIM.block.v1", &[&parent, &slot.to_le_bytes(), &n.id, &[variant]]),
+                    parent,
+                    slot,
+                    epoch,
+                    proposer: n.id,
+                    witness,
+                    sig: Vec::new(),
+                };
+                b.sig = sh.sign(&n.id, &b.header().signing_msg());
+                b
+            };
+            if n.behavior == Behavior::InvalidProof {
+                witness.stake_q = ONE_Q;
+                outbox.push((i, make(0, witness)));
+                continue;
+            }
+            let mut scratch = TrustState::default();
+            if verify_leader_with_witness(&sh.reg, snap, &sh.beacon, &mut scratch, &sh.params, epoch, slot, &witness).is_none() {
+                continue;
+            }
+            if n.behavior == Behavior::Equivocate {
+                outbox.push((i, make(1, witness.clone())));
+                self.equivocated.insert(n.id);
+            }
+            outbox.push((i, make(0, witness)));
+        }
+
+        self.proposed += outbox.len();
+        for (from, b) in outbox {
+            for to in 0..self.nodes.len() {
+                if self.partitioned(from, to) {
+                    self.delayed.push((to, b.clone()));
+                } else {
+                    self.nodes[to].receive(b.clone(), &self.shared);
+                }
+            }
+        }
+        self.slot += 1;
+    }
+
+    pub fn run(&mut self) -> SimReport {
+        while !self.is_done() { self.step(); }
+        self.report()
+    }
+
+    pub fn report(&self) -> SimReport {
+        SimReport {
+            heads: self.nodes.iter().map(|n| n.fc.head()).collect(),
+            head_heights: self.nodes.iter().map(|n| n.fc.height(&n.fc.head()).unwrap_or(0)).collect(),
+            proposed: self.proposed,
+            rejected: self.nodes.iter().map(|n| n.rejected).sum(),
+            equivocators: self.nodes.iter()
+                .filter(|n| n.behavior == Behavior::Honest)
+                .flat_map(|n| n.detected.iter().copied())
+                .collect(),
+            slashed_noreveal: self.slashed.clone(),
+            violations: self.check_invariants(),
+        }
+    }
+
+    /// Safety: honest nodes agree on the head once the network is whole, never
+    /// accept invalid-proof blocks and every equivocator is caught by all of
+    /// them; only RANDAO withholders are slashed. Liveness: the chain grew.
+    pub fn check_invariants(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        let honest: Vec<&SimNode> = self.nodes.iter().filter(|n| n.behavior == Behavior::Honest).collect();
+        let healed = self.cfg.partition.map(|p| self.slot >= p.until_slot).unwrap_or(true);
+
+        if healed {
+            if let Some(first) = honest.first() {
+                if honest.iter().any(|n| n.fc.head() != first.fc.head()) {
+                    out.push(format!("slot {}: honest nodes disagree on head", self.slot));
+                }
+                if self.slot >= self.cfg.slots_per_epoch && first.fc.height(&first.fc.head()) == Some(0) {
+                    out.push(format!("slot {}: chain did not grow", self.slot));
+                }
+            }
+        }
+        for n in &honest {
+            if n.proposer_of.values().any(|p| self.behavior_of(p) == Some(Behavior::InvalidProof)) {
+                out.push("honest node accepted a block with an invalid weight proof".into());
+            }
+            if healed && !self.equivocated.is_subset(&n.detected) {
+                out.push("equivocation not detected by an honest node".into());
+            }
+        }
+        for who in &self.slashed {
+            if self.behavior_of(who) != Some(Behavior::WithholdReveal) {
+                out.push("node slashed for no-reveal without withholding".into());
+            }
+        }
+        out
+    }
+
+    fn behavior_of(&self, who: &NodeId) -> Option<Behavior> {
+        self.nodes.iter().find(|n| n.id == *who).map(|n| n.behavior)
+    }
+}
+
+/// Build, run and report in one call
+pub fn simulate(cfg: SimConfig) -> Result<SimReport, &'static str> {
+    Ok(Simulation::new(cfg)?.run())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn honest_network_converges_and_is_deterministic() {
+        let cfg = SimConfig::new([1u8; 32], 32, vec![Behavior::Honest; 4]);
+        let a = simulate(cfg.clone()).unwrap();
+        let b = simulate(cfg).unwrap();
+        assert!(a.violations.is_empty(), "{:?}", a.violations);
+        assert_eq!(a.heads, b.heads);
+        assert!(a.head_heights[0] > 0);
+        assert!(a.slashed_noreveal.is_empty());
+    }
+
+    #[test]
+    fn byzantine_nodes_and_partition_keep_invariants() {
+        use Behavior::*;
+        let mut cfg = SimConfig::new([2u8; 32], 48, vec![Honest, Equivocate, Honest, WithholdReveal, Honest, InvalidProof]);
+        cfg.partition = Some(Partition { from_slot: 10, until_slot: 20, split: 3 });
+        let mut sim = Simulation::new(cfg).unwrap();
+
+        while sim.slot() < 15 { sim.step(); }
+        // w trakcie partycji głowy mogą się różnić, to nie jest naruszenie
+        assert!(sim.check_invariants().is_empty());
+
+        let r = sim.run();
+        assert!(r.violations.is_empty(), "{:?}", r.violations);
+        assert!(r.rejected > 0);
+        let n = |i: u64| kmac256_hash(b"SIM.node.v1", &[&[2u8; 32], &i.to_le_bytes()]);
+        assert!(r.slashed_noreveal.contains(&n(3)));
+        assert_eq!(r.equivocators, BTreeSet::from([n(1)]));
+    }
+}
@@ -0,0 +1,161 @@
+//! Slot clock
+//! Maps wall-clock milliseconds to slots from a fixed genesis time, tolerates
+//! blocks slightly from the future and estimates local clock drift as the
+//! weight-weighted median offset of validator timestamps. Only validators with
+//! weight in the current snapshot are sampled (one sample each), so cheap sybil
+//! peers cannot move the median, and the estimate is clamped to
+//! `MAX_CORRECTION_MS`. Proposing is refused while the estimated drift exceeds
+//! `max_drift_ms`.
+
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::pot::{EpochSnapshot, NodeId, Q};
+
+/// Upper bound on the drift estimate, whoever reports the timestamps
+pub const MAX_CORRECTION_MS: i64 = 30_000;
+
+/// Local wall clock in ms since UNIX epoch (0 if the clock is before 1970)
+pub fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
+
+pub struct SlotClock {
+    pub genesis_ms: u64,
+    pub slot_ms: u64,
+    pub future_tolerance_ms: u64,
+    pub max_drift_ms: u64,
+    /// validator -> (snapshot weight, validator_time - local_time)
+    offsets: HashMap<NodeId, (Q, i64)>,
+}
+
+impl SlotClock {
+    pub fn new(genesis_ms: u64, slot_ms: u64, future_tolerance_ms: u64, max_drift_ms: u64) -> Result<Self, &'static str> {
+        if slot_ms == 0 { return Err("slot_ms must be > 0"); }
+        if future_tolerance_ms >= slot_ms { return Err("future tolerance must be shorter than a slot"); }
+        Ok(Self { genesis_ms, slot_ms, future_tolerance_ms, max_drift_ms, offsets: HashMap::new() })
+    }
+
+    /// Slot at local time `now_ms` (None before genesis)
+    #[inline]
+    pub fn slot_at(&self, now_ms: u64) -> Option<u64> {
+        now_ms.checked_sub(self.genesis_ms).map(|d| d / self.slot_ms)
+    }
+
+    #[inline]
+    pub fn slot_start_ms(&self, slot: u64) -> u64 {
+        self.genesis_ms.saturating_add(slot.saturating_mul(self.slot_ms))
+    }
+
+    /// Whether a block for `slot` may be accepted at `now_ms`
+    /// (its slot has started, up to `future_tolerance_ms` early)
+    #[inline]
+    pub fn accepts_slot(&self, slot: u64, now_ms: u64) -> bool {
+        self.slot_start_ms(slot) <= now_ms.saturating_add(self.future_tolerance_ms)
+    }
+
+    /// Record a validator's signed timestamp (e.g. from a block header) seen at
+    /// `local_ms`, replacing its previous sample. Ignored (false) unless `who`
+    /// has weight in `snap`.
+    pub fn record_validator_time(&mut self, who: &NodeId, peer_ms: u64, local_ms: u64, snap: &EpochSnapshot) -> bool {
+        let w = snap.weight_q_of(who);
+        if w == 0 { return false; }
+        let off = (i128::from(peer_ms) - i128::from(local_ms)).clamp(i128::from(i64::MIN), i128::from(i64::MAX)) as i64;
+        self.offsets.insert(*who, (w, off));
+        true
+    }
+
+    /// Re-weight samples with a new epoch's snapshot; validators without weight are dropped
+    pub fn on_snapshot(&mut self, snap: &EpochSnapshot) {
+        self.offsets.retain(|who, (w, _)| {
+            *w = snap.weight_q_of(who);
+            *w > 0
+        });
+    }
+
+    /// Weighted median offset in ms (positive = local clock is behind), clamped
+    /// to ±`MAX_CORRECTION_MS`; None without samples
+    pub fn drift_ms(&self) -> Option<i64> {
+        let mut v: Vec<(i64, Q)> = self.offsets.values().map(|(w, off)| (*off, *w)).collect();
+        v.sort_unstable();
+        let total: u128 = v.iter().map(|(_, w)| u128::from(*w)).sum();
+        let mut acc = 0u128;
+        let (median, _) = v.into_iter().find(|(_, w)| {
+            acc += u128::from(*w);
+            acc * 2 >= total
+        })?;
+        Some(median.clamp(-MAX_CORRECTION_MS, MAX_CORRECTION_MS))
+    }
+
+    #[inline]
+    pub fn drift_exceeded(&self) -> bool {
+        self.drift_ms().map(|d| d.unsigned_abs() > self.max_drift_ms).unwrap_or(false)
+    }
+
+    /// Slot to propose in at `now_ms`, refusing while drift is too large
+    pub fn proposal_slot(&self, now_ms: u64) -> Result<u64, &'static str> {
+        if self.drift_exceeded() { return Err("local clock drift exceeds limit"); }
+        self.slot_at(now_ms).ok_or("before genesis")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pot::{Registry, TrustParams, TrustState, ONE_Q};
+
+    #[test]
+    fn slots_and_future_tolerance() {
+        let c = SlotClock::new(1_000, 500, 100, 200).unwrap();
+        assert_eq!(c.slot_at(999), None);
+        assert_eq!(c.slot_at(1_000), Some(0));
+        assert_eq!(c.slot_at(1_499), Some(0));
+        assert_eq!(c.slot_at(1_500), Some(1));
+        assert!(c.accepts_slot(1, 1_400));
+        assert!(!c.accepts_slot(1, 1_399));
+        assert!(SlotClock::new(0, 500, 500, 0).is_err());
+    }
+
+    fn snapshot(stakes: &[(u8, u64)]) -> EpochSnapshot {
+        let tp = TrustParams { alpha_q: ONE_Q, beta_q: 0, init_q: ONE_Q };
+        let mut reg = Registry::default();
+        for (n, stake) in stakes { reg.insert([*n; 32], *stake, true); }
+        EpochSnapshot::build(0, &reg, &TrustState::default(), &tp, 0)
+    }
+
+    #[test]
+    fn drift_uses_median_and_blocks_proposals() {
+        let snap = snapshot(&[(0, 100), (1, 100), (2, 100), (3, 100), (4, 100), (5, 100), (6, 100)]);
+        let mut c = SlotClock::new(0, 1_000, 100, 200).unwrap();
+        assert_eq!(c.drift_ms(), None);
+        assert_eq!(c.proposal_slot(5_000), Ok(5));
+        // jeden walidator z zepsutym zegarem nie przesuwa mediany
+        for (n, t) in [10_050, 10_060, 99_000].into_iter().enumerate() {
+            assert!(c.record_validator_time(&[n as u8; 32], t, 10_000, &snap));
+        }
+        assert_eq!(c.drift_ms(), Some(60));
+        assert!(c.proposal_slot(10_000).is_ok());
+        for n in 3..7u8 { c.record_validator_time(&[n; 32], 9_000, 10_000, &snap); }
+        assert_eq!(c.drift_ms(), Some(-1_000));
+        assert!(c.proposal_slot(10_000).is_err());
+    }
+
+    #[test]
+    fn sybils_and_light_validators_cannot_own_the_median() {
+        let snap = snapshot(&[(1, 1_000), (2, 10), (3, 10)]);
+        let mut c = SlotClock::new(0, 1_000, 100, 200).unwrap();
+        assert!(c.record_validator_time(&[1u8; 32], 10_010, 10_000, &snap));
+        // peery spoza snapshotu są ignorowane, ile by ich nie było
+        for n in 10..200u8 { assert!(!c.record_validator_time(&[n; 32], 90_000, 10_000, &snap)); }
+        // dwóch lekkich walidatorów przegrywa z jednym ciężkim
+        c.record_validator_time(&[2u8; 32], 90_000, 10_000, &snap);
+        c.record_validator_time(&[3u8; 32], 90_000, 10_000, &snap);
+        assert_eq!(c.drift_ms(), Some(10));
+        assert!(c.proposal_slot(10_000).is_ok());
+
+        // nowy snapshot bez walidatora 1: zostają lekcy, ale korekta jest ograniczona
+        c.on_snapshot(&snapshot(&[(2, 10), (3, 10)]));
+        assert_eq!(c.offsets.len(), 2);
+        assert_eq!(c.drift_ms(), Some(MAX_CORRECTION_MS));
+    }
+}
@@ -1,8 +1,8 @@
 //! Snapshot witness verification module
 //! Provides compact witness format for weight verification
 
-use crate::pot::{EpochSnapshot, MerkleProof, NodeId, Q, StakeQ};
-use crate::crypto_kmac_consensus::kmac256_hash;
+use crate::pot::{merkle_leaf_hash, verify_merkle, EpochSnapshot, MerkleProof, NodeId, Q, StakeQ};
+use std::collections::BTreeMap;
 
 /// Compact weight witness format (V1)
 /// Contains minimal information needed to verify a node's weight in an epoch snapshot
@@ -47,32 +47,120 @@ impl SnapshotWitnessExt for EpochSnapshot {
     }
 }
 
-#[inline]
-fn merkle_leaf_hash(who: &NodeId, stake_q: StakeQ, trust_q: Q) -> [u8; 32] {
-    kmac256_hash(b"WGT.v1", &[
-        who,
-        &stake_q.to_le_bytes(),
-        &trust_q.to_le_bytes(),
-    ])
+/// Compact per-epoch snapshot digest exchanged between nodes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SnapshotDigest {
+    pub epoch: u64,
+    pub weights_root: [u8; 32],
+    pub sum_weights_q: Q,
+    pub validator_count: u32,
 }
 
-#[inline]
-fn merkle_parent(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
-    kmac256_hash(b"MRK.v1", &[a, b])
+pub const SNAPSHOT_DIGEST_LEN: usize = 8 + 32 + 8 + 4;
+
+impl SnapshotDigest {
+    pub fn to_bytes(&self) -> [u8; SNAPSHOT_DIGEST_LEN] {
+        let mut out = [0u8; SNAPSHOT_DIGEST_LEN];
+        out[..8].copy_from_slice(&self.epoch.to_le_bytes());
+        out[8..40].copy_from_slice(&self.weights_root);
+        out[40..48].copy_from_slice(&self.sum_weights_q.to_le_bytes());
+        out[48..].copy_from_slice(&self.validator_count.to_le_bytes());
+        out
+    }
+
+    pub fn from_bytes(b: &[u8]) -> Option<Self> {
+        if b.len() != SNAPSHOT_DIGEST_LEN { return None; }
+        let mut weights_root = [0u8; 32];
+        weights_root.copy_from_slice(&b[8..40]);
+        Some(Self {
+            epoch: u64::from_le_bytes(b[..8].try_into().ok()?),
+            weights_root,
+            sum_weights_q: u64::from_le_bytes(b[40..48].try_into().ok()?),
+            validator_count: u32::from_le_bytes(b[48..].try_into().ok()?),
+        })
+    }
 }
 
-fn verify_merkle(proof: &MerkleProof, leaf: [u8; 32], root: [u8; 32]) -> bool {
-    let mut acc = leaf;
-    let mut idx = proof.leaf_index;
-    for sib in &proof.siblings {
-        acc = if (idx & 1) == 0 {
-            merkle_parent(&acc, sib)
-        } else {
-            merkle_parent(sib, &acc)
-        };
-        idx >>= 1;
+/// Why a peer's digest differs from ours (first difference found)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DigestMismatch {
+    Epoch { local: u64, remote: u64 },
+    ValidatorCount { local: u32, remote: u32 },
+    SumWeights { local: Q, remote: Q },
+    WeightsRoot { local: [u8; 32], remote: [u8; 32] },
+}
+
+/// Entry-level difference between our snapshot and a peer's leaf list
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EntryDiff {
+    /// Leaf hash differs (stake_q or trust_q disagree)
+    Changed(NodeId),
+    /// Validator present only in our snapshot
+    MissingRemote(NodeId),
+    /// Validator present only in the peer's snapshot
+    MissingLocal(NodeId),
+}
+
+/// Extension trait for EpochSnapshot to compare state with peers
+pub trait SnapshotDigestExt {
+    fn digest(&self) -> SnapshotDigest;
+    /// Compare against a peer digest; Err carries the first differing field
+    fn check_digest(&self, remote: &SnapshotDigest) -> Result<(), DigestMismatch>;
+    /// Leaf hashes in `order`, served to peers after a digest mismatch
+    fn leaf_hashes(&self) -> Vec<(NodeId, [u8; 32])>;
+    /// Pinpoint which validator entries differ from a peer's leaf list
+    fn diff_entries(&self, remote: &[(NodeId, [u8; 32])]) -> Vec<EntryDiff>;
+}
+
+impl SnapshotDigestExt for EpochSnapshot {
+    fn digest(&self) -> SnapshotDigest {
+        SnapshotDigest {
+            epoch: self.epoch,
+            weights_root: self.weights_root,
+            sum_weights_q: self.sum_weights_q,
+            validator_count: self.order.len() as u32,
+        }
+    }
+
+    fn check_digest(&self, remote: &SnapshotDigest) -> Result<(), DigestMismatch> {
+        let local = self.digest();
+        if local.epoch != remote.epoch {
+            return Err(DigestMismatch::Epoch { local: local.epoch, remote: remote.epoch });
+        }
+        if local.validator_count != remote.validator_count {
+            return Err(DigestMismatch::ValidatorCount { local: local.validator_count, remote: remote.validator_count });
+        }
+        if local.sum_weights_q != remote.sum_weights_q {
+            return Err(DigestMismatch::SumWeights { local: local.sum_weights_q, remote: remote.sum_weights_q });
+        }
+        if local.weights_root != remote.weights_root {
+            return Err(DigestMismatch::WeightsRoot { local: local.weights_root, remote: remote.weights_root });
+        }
+        Ok(())
+    }
+
+    fn leaf_hashes(&self) -> Vec<(NodeId, [u8; 32])> {
+        self.order.iter()
+            .map(|id| (*id, merkle_leaf_hash(id, self.stake_q_of(id), self.trust_q_of(id))))
+            .collect()
+    }
+
+    fn diff_entries(&self, remote: &[(NodeId, [u8; 32])]) -> Vec<EntryDiff> {
+        let local: BTreeMap<NodeId, [u8; 32]> = self.leaf_hashes().into_iter().collect();
+        let remote: BTreeMap<NodeId, [u8; 32]> = remote.iter().copied().collect();
+        let mut out = Vec::new();
+        for (id, h) in &local {
+            match remote.get(id) {
+                None => out.push(EntryDiff::MissingRemote(*id)),
+                Some(r) if r != h => out.push(EntryDiff::Changed(*id)),
+                _ => {}
+            }
+        }
+        for id in remote.keys() {
+            if !local.contains_key(id) { out.push(EntryDiff::MissingLocal(*id)); }
+        }
+        out
     }
-    acc == root
 }
 
 #[cfg(test)]
@@ -145,4 +233,33 @@ mod tests {
         wit.stake_q = 0;
         assert!(!snap.verify_witness(&wit));
     }
+
+    #[test]
+    fn digest_mismatch_pinpoints_entry() {
+        let tp = TrustParams {
+            alpha_q: ONE_Q,
+            beta_q: 0,
+            init_q: ONE_Q,
+        };
+        let mut reg = Registry::default();
+        let (a, b) = (nid(1), nid(2));
+        reg.insert(a, 100, true);
+        reg.insert(b, 100, true);
+
+        let mut ts1 = TrustState::default();
+        ts1.set(a, ONE_Q);
+        ts1.set(b, ONE_Q);
+        let mut ts2 = TrustState::default();
+        ts2.set(a, ONE_Q);
+        ts2.set(b, q_from_basis_points(5000));
+
+        let local = EpochSnapshot::build(3, &reg, &ts1, &tp, 0);
+        let remote = EpochSnapshot::build(3, &reg, &ts2, &tp, 0);
+
+        let d = local.digest();
+        assert_eq!(SnapshotDigest::from_bytes(&d.to_bytes()), Some(d));
+        assert!(local.check_digest(&d).is_ok());
+        assert!(matches!(local.check_digest(&remote.digest()), Err(DigestMismatch::SumWeights { .. })));
+        assert_eq!(local.diff_entries(&remote.leaf_hashes()), vec![EntryDiff::Changed(b)]);
+    }
 }
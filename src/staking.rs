@@ -0,0 +1,227 @@
+//! Validator set changes: bond, unbond, rebond
+//! Operations are queued and take effect at the next epoch boundary, right
+//! before the snapshot of that epoch is built (see `EpochManager::with_staking`).
+//! Unbonded stake stays slashable until `unbonding_epochs` have passed.
+
+use std::collections::BTreeMap;
+
+use crate::pot::{NodeId, Registry};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StakingOp {
+    Bond { who: NodeId, amount: u64 },
+    Unbond { who: NodeId, amount: u64 },
+    /// Move stake that is still unbonding back to the active bond
+    Rebond { who: NodeId, amount: u64 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UnbondingEntry {
+    pub amount: u64,
+    pub release_epoch: u64,
+}
+
+/// What happened to the registry at one epoch boundary
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StakingChanges {
+    /// Validators that became active
+    pub activated: Vec<NodeId>,
+    /// Validators whose whole stake is now unbonding
+    pub exited: Vec<NodeId>,
+    /// Stake that finished unbonding and can be withdrawn by the caller
+    pub released: Vec<(NodeId, u64)>,
+}
+
+pub struct Staking {
+    pub unbonding_epochs: u64,
+    bond_queue: BTreeMap<NodeId, u64>,
+    unbond_queue: BTreeMap<NodeId, u64>,
+    unbonding: BTreeMap<NodeId, Vec<UnbondingEntry>>,
+}
+
+impl Staking {
+    pub fn new(unbonding_epochs: u64) -> Self {
+        Self {
+            unbonding_epochs,
+            bond_queue: BTreeMap::new(),
+            unbond_queue: BTreeMap::new(),
+            unbonding: BTreeMap::new(),
+        }
+    }
+
+    /// Stake queued for activation at the next boundary
+    #[inline]
+    pub fn queued_bond(&self, who: &NodeId) -> u64 {
+        self.bond_queue.get(who).copied().unwrap_or(0)
+    }
+
+    /// Stake still locked in the unbonding period (including queued unbonds)
+    pub fn unbonding_total(&self, who: &NodeId) -> u64 {
+        let locked: u64 = self.unbonding.get(who)
+            .map(|v| v.iter().map(|u| u.amount).sum())
+            .unwrap_or(0);
+        locked.saturating_add(self.unbond_queue.get(who).copied().unwrap_or(0))
+    }
+
+    /// Validate and queue a staking operation (executed by the block's state transition)
+    pub fn apply(&mut self, op: StakingOp, reg: &Registry) -> Result<(), &'static str> {
+        match op {
+            StakingOp::Bond { who, amount } => {
+                if amount == 0 { return Err("bond amount must be > 0"); }
+                let q = self.bond_queue.entry(who).or_insert(0);
+                *q = q.checked_add(amount).ok_or("bond overflow")?;
+            }
+            StakingOp::Unbond { who, amount } => {
+                if amount == 0 { return Err("unbond amount must be > 0"); }
+                let queued = self.unbond_queue.get(&who).copied().unwrap_or(0);
+                let free = reg.stake(&who).saturating_sub(queued);
+                if amount > free { return Err("unbond exceeds bonded stake"); }
+                self.unbond_queue.insert(who, queued + amount);
+            }
+            StakingOp::Rebond { who, amount } => {
+                if amount == 0 { return Err("rebond amount must be > 0"); }
+                if amount > self.unbonding_total(&who) { return Err("rebond exceeds unbonding stake"); }
+                // najpierw anuluj unbond z kolejki (stake wciąż w rejestrze)
+                let mut left = amount;
+                if let Some(q) = self.unbond_queue.get_mut(&who) {
+                    let take = left.min(*q);
+                    *q -= take;
+                    left -= take;
+                    if *q == 0 { self.unbond_queue.remove(&who); }
+                }
+                if left == 0 { return Ok(()); }
+                // potem najmłodsze wpisy unbonding – wracają do bondu od następnej epoki
+                let v = self.unbonding.get_mut(&who).ok_or("rebond exceeds unbonding stake")?;
+                let mut from_locked = 0u64;
+                while left > 0 {
+                    let last = v.last_mut().ok_or("rebond exceeds unbonding stake")?;
+                    let take = left.min(last.amount);
+                    last.amount -= take;
+                    left -= take;
+                    from_locked += take;
+                    if last.amount == 0 { v.pop(); }
+                }
+                if v.is_empty() { self.unbonding.remove(&who); }
+                *self.bond_queue.entry(who).or_insert(0) += from_locked;
+            }
+        }
+        Ok(())
+    }
+
+    /// Slash stake that is still unbonding (same bps as the bonded part);
+    /// `EpochManager` calls this for every no-reveal and equivocation slash
+    pub fn slash_unbonding(&mut self, who: &NodeId, bps: u32) {
+        let bps = u128::from(bps.min(10_000));
+        let cut = |a: u64| a - ((u128::from(a) * bps) / 10_000) as u64;
+        if let Some(q) = self.unbond_queue.get_mut(who) { *q = cut(*q); }
+        if let Some(v) = self.unbonding.get_mut(who) {
+            for u in v.iter_mut() { u.amount = cut(u.amount); }
+        }
+    }
+
+    /// Apply queued operations at the boundary into `epoch`.
+    /// Call before `EpochSnapshot::build` for that epoch.
+    pub fn on_epoch(&mut self, epoch: u64, reg: &mut Registry) -> StakingChanges {
+        let mut ch = StakingChanges::default();
+
+        for (who, amount) in std::mem::take(&mut self.unbond_queue) {
+            let Some(e) = reg.map.get_mut(&who) else { continue };
+            // stake mógł zostać w międzyczasie obcięty przez slashing
+            let amount = amount.min(e.stake);
+            e.stake -= amount;
+            if amount > 0 {
+                self.unbonding.entry(who).or_default()
+                    .push(UnbondingEntry { amount, release_epoch: epoch + self.unbonding_epochs });
+            }
+            if e.stake == 0 && e.active {
+                e.active = false;
+                ch.exited.push(who);
+            }
+        }
+
+        for (who, amount) in std::mem::take(&mut self.bond_queue) {
+            match reg.map.get_mut(&who) {
+                Some(e) => {
+                    e.stake = e.stake.saturating_add(amount);
+                    if !e.active {
+                        e.active = true;
+                        ch.activated.push(who);
+                    }
+                }
+                None => {
+                    reg.insert(who, amount, true);
+                    ch.activated.push(who);
+                }
+            }
+        }
+        // wyjście i ponowny bond w tej samej epoce = brak zmiany netto
+        let both: Vec<NodeId> = ch.exited.iter().filter(|w| ch.activated.contains(w)).copied().collect();
+        ch.exited.retain(|w| !both.contains(w));
+        ch.activated.retain(|w| !both.contains(w));
+
+        self.unbonding.retain(|who, v| {
+            let released: u64 = v.iter().filter(|u| u.release_epoch <= epoch).map(|u| u.amount).sum();
+            if released > 0 { ch.released.push((*who, released)); }
+            v.retain(|u| u.release_epoch > epoch);
+            !v.is_empty()
+        });
+        ch
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nid(n: u8) -> NodeId {
+        let mut id = [0u8; 32];
+        id[0] = n;
+        id
+    }
+
+    #[test]
+    fn join_exit_and_release() {
+        let (a, b) = (nid(1), nid(2));
+        let mut reg = Registry::default();
+        reg.insert(a, 1000, true);
+        let mut st = Staking::new(2);
+
+        st.apply(StakingOp::Bond { who: b, amount: 500 }, &reg).unwrap();
+        assert!(!reg.map.contains_key(&b)); // dopiero na granicy epoki
+        st.apply(StakingOp::Unbond { who: a, amount: 1000 }, &reg).unwrap();
+        assert!(st.apply(StakingOp::Unbond { who: a, amount: 1 }, &reg).is_err());
+
+        let ch = st.on_epoch(1, &mut reg);
+        assert_eq!(ch.activated, vec![b]);
+        assert_eq!(ch.exited, vec![a]);
+        assert!(!reg.is_active(&a, 0));
+        assert_eq!(reg.stake(&b), 500);
+        assert_eq!(st.unbonding_total(&a), 1000);
+
+        st.slash_unbonding(&a, 1000);
+        assert!(st.on_epoch(2, &mut reg).released.is_empty());
+        assert_eq!(st.on_epoch(3, &mut reg).released, vec![(a, 900)]);
+        assert_eq!(st.unbonding_total(&a), 0);
+    }
+
+    #[test]
+    fn rebond_cancels_unbonding() {
+        let a = nid(1);
+        let mut reg = Registry::default();
+        reg.insert(a, 1000, true);
+        let mut st = Staking::new(5);
+
+        st.apply(StakingOp::Unbond { who: a, amount: 400 }, &reg).unwrap();
+        st.apply(StakingOp::Rebond { who: a, amount: 100 }, &reg).unwrap();
+        st.on_epoch(1, &mut reg);
+        assert_eq!(reg.stake(&a), 700);
+        assert_eq!(st.unbonding_total(&a), 300);
+
+        st.apply(StakingOp::Rebond { who: a, amount: 300 }, &reg).unwrap();
+        assert!(st.apply(StakingOp::Rebond { who: a, amount: 1 }, &reg).is_err());
+        assert_eq!(st.queued_bond(&a), 300);
+        let ch = st.on_epoch(2, &mut reg);
+        assert_eq!(reg.stake(&a), 1000);
+        assert!(ch.activated.is_empty() && ch.released.is_empty());
+    }
+}
@@ -0,0 +1,223 @@
+//! Bounded history proofs ("proof of trust age")
+//! Lets a validator prove it kept trust >= threshold over the last N epochs
+//! against a single history root committing to per-epoch snapshot roots.
+
+use crate::pot::{
+    merkle_build_proof, merkle_leaf_hash, merkle_root, verify_merkle, EpochSnapshot,
+    MerkleProof, NodeId, Q,
+};
+use crate::crypto_kmac_consensus::kmac256_hash;
+use crate::snapshot::WeightWitnessV1;
+
+/// Append-only commitment to consecutive epoch snapshots
+#[derive(Clone, Debug)]
+pub struct SnapshotHistory {
+    pub first_epoch: u64,
+    leaves: Vec<[u8; 32]>,
+}
+
+/// One epoch of a trust-age proof
+#[derive(Clone, Debug)]
+pub struct TrustAgeEntry {
+    pub epoch: u64,
+    pub weights_root: [u8; 32],
+    pub sum_weights_q: Q,
+    pub weight: WeightWitnessV1,   // liść `who` w weights_root
+    pub history: MerkleProof,      // liść epoki w history root
+}
+
+/// Proof that `who` held trust over consecutive epochs (oldest first)
+#[derive(Clone, Debug)]
+pub struct TrustAgeProof {
+    pub who: NodeId,
+    pub entries: Vec<TrustAgeEntry>,
+}
+
+#[inline]
+fn history_leaf_hash(epoch: u64, weights_root: &[u8; 32], sum_weights_q: Q) -> [u8; 32] {
+    kmac256_hash(b"HIST.v1", &[
+        &epoch.to_le_bytes(),
+        weights_root,
+        &sum_weights_q.to_le_bytes(),
+    ])
+}
+
+impl SnapshotHistory {
+    pub fn new(first_epoch: u64) -> Self {
+        Self { first_epoch, leaves: Vec::new() }
+    }
+
+    /// Append the snapshot of the next epoch (epochs must be consecutive)
+    pub fn push(&mut self, snap: &EpochSnapshot) -> Result<(), &'static str> {
+        let expected = self.first_epoch + self.leaves.len() as u64;
+        if snap.epoch != expected {
+            return Err("snapshot epoch is not the next epoch in history");
+        }
+        self.leaves.push(history_leaf_hash(snap.epoch, &snap.weights_root, snap.sum_weights_q));
+        Ok(())
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize { self.leaves.len() }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.leaves.is_empty() }
+
+    /// Last committed epoch, if any
+    pub fn head_epoch(&self) -> Option<u64> {
+        if self.leaves.is_empty() { None } else { Some(self.first_epoch + self.leaves.len() as u64 - 1) }
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        merkle_root(&self.leaves)
+    }
+
+    pub fn prove_epoch(&self, epoch: u64) -> Option<MerkleProof> {
+        let idx = epoch.checked_sub(self.first_epoch)?;
+        if idx >= self.leaves.len() as u64 { return None; }
+        Some(merkle_build_proof(&self.leaves, idx))
+    }
+
+    /// Buduje dowód dla ostatnich `n` epok historii; `snaps` musi zawierać te epoki.
+    /// Zwraca None, jeśli `who` nie ma liścia w którejś z nich lub trust < threshold.
+    pub fn prove_trust_age(
+        &self,
+        snaps: &[EpochSnapshot],
+        who: &NodeId,
+        threshold_q: Q,
+        n: u64,
+    ) -> Option<TrustAgeProof> {
+        let head = self.head_epoch()?;
+        if n == 0 || n > self.leaves.len() as u64 { return None; }
+
+        let mut entries = Vec::with_capacity(n as usize);
+        for epoch in (head + 1 - n)..=head {
+            let snap = snaps.iter().find(|s| s.epoch == epoch)?;
+            let trust_q = snap.trust_q_of(who);
+            if trust_q < threshold_q { return None; }
+            let proof = snap.build_proof(who)?;
+            entries.push(TrustAgeEntry {
+                epoch,
+                weights_root: snap.weights_root,
+                sum_weights_q: snap.sum_weights_q,
+                weight: WeightWitnessV1 {
+                    who: *who,
+                    stake_q: snap.stake_q_of(who),
+                    trust_q,
+                    leaf_index: proof.leaf_index,
+                    siblings: proof.siblings,
+                },
+                history: self.prove_epoch(epoch)?,
+            });
+        }
+        Some(TrustAgeProof { who: *who, entries })
+    }
+}
+
+/// Verify a trust-age proof against a trusted history root and head epoch.
+/// Needs no snapshot data: only the root and the epoch it was committed at.
+pub fn verify_trust_age(
+    proof: &TrustAgeProof,
+    history_root: [u8; 32],
+    head_epoch: u64,
+    min_epochs: u64,
+    threshold_q: Q,
+) -> bool {
+    if min_epochs == 0 || (proof.entries.len() as u64) < min_epochs { return false; }
+
+    let Some(first) = head_epoch.saturating_add(1).checked_sub(proof.entries.len() as u64) else { return false };
+    for (i, e) in proof.entries.iter().enumerate() {
+        // kolejne epoki kończące się na head_epoch
+        if e.epoch != first + i as u64 { return false; }
+        if e.weight.who != proof.who { return false; }
+        if e.weight.trust_q < threshold_q { return false; }
+
+        let leaf = merkle_leaf_hash(&e.weight.who, e.weight.stake_q, e.weight.trust_q);
+        let wproof = MerkleProof { leaf_index: e.weight.leaf_index, siblings: e.weight.siblings.clone() };
+        if !verify_merkle(&wproof, leaf, e.weights_root) { return false; }
+
+        let hleaf = history_leaf_hash(e.epoch, &e.weights_root, e.sum_weights_q);
+        if !verify_merkle(&e.history, hleaf, history_root) { return false; }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pot::{Registry, TrustParams, TrustState, q_from_basis_points};
+
+    fn nid(n: u8) -> NodeId {
+        let mut id = [0u8; 32];
+        id[0] = n;
+        id
+    }
+
+    fn history_with(trust_a: &[u32]) -> (SnapshotHistory, Vec<EpochSnapshot>, NodeId) {
+        let tp = TrustParams {
+            alpha_q: q_from_basis_points(9900),
+            beta_q: q_from_basis_points(100),
+            init_q: q_from_basis_points(1000),
+        };
+        let mut reg = Registry::default();
+        let a = nid(1);
+        let b = nid(2);
+        reg.insert(a, 100, true);
+        reg.insert(b, 300, true);
+
+        let mut hist = SnapshotHistory::new(10);
+        let mut snaps = Vec::new();
+        for (i, bp) in trust_a.iter().enumerate() {
+            let mut ts = TrustState::default();
+            ts.set(a, q_from_basis_points(*bp));
+            ts.set(b, q_from_basis_points(5000));
+            let snap = EpochSnapshot::build(10 + i as u64, &reg, &ts, &tp, 0);
+            hist.push(&snap).unwrap();
+            snaps.push(snap);
+        }
+        (hist, snaps, a)
+    }
+
+    #[test]
+    fn trust_age_roundtrip() {
+        let (hist, snaps, a) = history_with(&[2000, 8000, 8000, 9000]);
+        let thr = q_from_basis_points(7000);
+        let head = hist.head_epoch().unwrap();
+        assert_eq!(head, 13);
+
+        let proof = hist.prove_trust_age(&snaps, &a, thr, 3).unwrap();
+        assert!(verify_trust_age(&proof, hist.root(), head, 3, thr));
+        // za mało epok
+        assert!(!verify_trust_age(&proof, hist.root(), head, 4, thr));
+        // nieaktualny head
+        assert!(!verify_trust_age(&proof, hist.root(), head + 1, 3, thr));
+        // epoka 10 ma trust poniżej progu
+        assert!(hist.prove_trust_age(&snaps, &a, thr, 4).is_none());
+    }
+
+    #[test]
+    fn trust_age_rejects_tampering() {
+        let (hist, snaps, a) = history_with(&[8000, 8000, 8000]);
+        let thr = q_from_basis_points(7000);
+        let head = hist.head_epoch().unwrap();
+        let proof = hist.prove_trust_age(&snaps, &a, thr, 3).unwrap();
+
+        let mut p1 = proof.clone();
+        p1.entries[1].weight.trust_q += 1;
+        assert!(!verify_trust_age(&p1, hist.root(), head, 3, thr));
+
+        let mut p2 = proof.clone();
+        p2.entries[0].sum_weights_q += 1;
+        assert!(!verify_trust_age(&p2, hist.root(), head, 3, thr));
+
+        let mut p3 = proof;
+        p3.entries.swap(0, 1);
+        assert!(!verify_trust_age(&p3, hist.root(), head, 3, thr));
+    }
+
+    #[test]
+    fn history_requires_consecutive_epochs() {
+        let (mut hist, snaps, _) = history_with(&[8000]);
+        assert!(hist.push(&snaps[0]).is_err());
+    }
+}